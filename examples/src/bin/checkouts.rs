@@ -29,14 +29,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let checkout: CheckoutsAPI = intasend.checkout();
     // println!("[#] Checkout instance: {:#?}", checkout);
 
-    let checkout_req = CheckoutRequest {
-        first_name: Some("Joe".to_string()),
-        last_name: Some("Doe".to_string()),
-        email: Some("joe@doe.com".to_string()),
-        method: Some(CheckoutMethod::CardPayment),
-        amount: Decimal::new(10000, 2), // 100.00
-        currency: Currency::Kes,
-    };
+    let checkout_req = CheckoutRequest::builder(Decimal::new(10000, 2), Currency::Kes) // 100.00
+        .customer("Joe", "Doe", "joe@doe.com")
+        .method(CheckoutMethod::CardPayment)
+        .redirect_url("https://example.com/thank-you")
+        .build();
     println!("[#] Checkout request: {:#?}", checkout_req);
     println!(
         "[#] Checkout request (JSON): {:#?}",
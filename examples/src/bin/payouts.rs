@@ -50,8 +50,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         device_id: None,
         callback_url: None,
         batch_reference: None,
+        requires_approval: Some(PayoutApproval::Yes),
         transactions,
-        // requires_approval: PayoutApproval::Yes,
     };
 
     let mpesa_payout = payouts.mpesa_b2c(payout_request.clone()).await?;
@@ -0,0 +1,496 @@
+#![allow(unused)]
+#![allow(unused_imports)]
+
+//! A blocking (synchronous) IntaSend client.
+//!
+//! The blocking client mirrors the async `Intasend` client and its `*API` facades one
+//! to one, but every method waits for the response before returning. It owns a small
+//! single threaded runtime which drives the async client under the hood, so no runtime
+//! has to be set up by the caller.
+//!
+//! **Note**: the blocking client must not be used from within an async runtime, calling
+//! any of its methods from inside an async context panics.
+//!
+//! ```rust,no_run
+//! # fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! // Load .env file
+//! dotenvy::dotenv().ok();
+//!
+//! let intasend_public_key = std::env::var("INTASEND_PUBLIC_KEY").expect("INTASEND_PUBLIC_KEY must be set");
+//! let intasend_secret_key = std::env::var("INTASEND_SECRET_KEY").expect("INTASEND_SECRET_KEY must be set");
+//!
+//! // Blocking Intasend Client
+//! let intasend = intasend::blocking::Intasend::new(
+//!    intasend_public_key,
+//!    intasend_secret_key,
+//!     true,
+//! );
+//!
+//! // WalletsAPI
+//! let wallets_api: intasend::blocking::WalletsAPI = intasend.wallets();
+//!
+//! let wallet_list_info: intasend::WalletListResponse = wallets_api.list()?;
+//! println!("[#] Wallet List Info: {:#?}", wallet_list_info);
+//!
+//! Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::Result;
+use tokio::runtime::{Builder, Runtime};
+
+use super::checkout::{
+    CheckoutDetailsRequest, CheckoutDetailsResponse, CheckoutRequest, CheckoutResponse,
+    CheckoutWaitOptions,
+};
+use super::checkout_store::CheckoutStore;
+use super::collection::{
+    MpesaStkPushRequest, MpesaStkPushResponse, StkPushStatusRequest, StkPushStatusResponse,
+};
+use super::payment_link_analytics::PaymentLinkAnalytics;
+use super::payment_links::{
+    PaymentLink, PaymentLinksCreateDetails, PaymentLinksListResponse, PaymentLinksUpdateDetails,
+};
+use super::payouts::approval::{ApprovalRecord, PayoutApprovalPolicy, PendingPayout};
+use super::payouts::batch::PayoutBatchReport;
+use super::payouts::{
+    BankCodes, Payout, PayoutApprovalRequest, PayoutCancelRequest, PayoutRequest,
+    PayoutStatusRequest,
+};
+use super::refunds::{Refund, RefundListResponse, RefundRequest};
+use super::wallets::{
+    FundCheckoutRequest, FundCheckoutResponse, FundMpesaRequest, FundMpesaResponse, Wallet,
+    WalletCreateDetails, WalletIntraTransferRequest, WalletIntraTransferResponse,
+    WalletListResponse, WalletTransactionsResponse,
+};
+
+/// The blocking counterpart of [`crate::Intasend`].
+///
+/// Cloning the client is cheap, all the clones share the same runtime.
+#[derive(Clone, Debug)]
+pub struct Intasend {
+    inner: crate::Intasend,
+    runtime: Arc<Runtime>,
+}
+
+impl Intasend {
+    /// The `new` method creates a new instance of the blocking `Intasend` client
+    ///
+    /// ```rust,no_run
+    /// // Load .env file
+    /// dotenvy::dotenv().ok();
+    ///
+    /// let intasend_public_key = std::env::var("INTASEND_PUBLIC_KEY").expect("INTASEND_PUBLIC_KEY must be set");
+    /// let intasend_secret_key = std::env::var("INTASEND_SECRET_KEY").expect("INTASEND_SECRET_KEY must be set");
+    ///
+    /// // Blocking Intasend Client
+    /// let intasend = intasend::blocking::Intasend::new(
+    ///    intasend_public_key,
+    ///    intasend_secret_key,
+    ///     true,
+    /// );
+    /// println!("[#] Intasend instance: {:#?}", intasend);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the internal runtime cannot be created.
+    pub fn new(publishable_key: String, secret_key: String, test_mode: bool) -> Self {
+        Self::from(crate::Intasend::new(publishable_key, secret_key, test_mode))
+    }
+
+    /// The `collection` method returns an instance of the blocking `CollectionsAPI` struct
+    #[cfg(feature = "server")]
+    pub fn collection(&self) -> CollectionsAPI {
+        CollectionsAPI {
+            inner: self.inner.collection(),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// The `checkout` method returns an instance of the blocking `CheckoutsAPI` struct
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub fn checkout(&self) -> CheckoutsAPI {
+        CheckoutsAPI {
+            inner: self.inner.checkout(),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// The `payouts` method returns an instance of the blocking `PayoutsAPI` struct
+    #[cfg(feature = "server")]
+    pub fn payouts(&self) -> PayoutsAPI {
+        PayoutsAPI {
+            inner: self.inner.payouts(),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// The `refunds` method returns an instance of the blocking `RefundsAPI` struct
+    #[cfg(feature = "server")]
+    pub fn refunds(&self) -> RefundsAPI {
+        RefundsAPI {
+            inner: self.inner.refunds(),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// The `wallets` method returns an instance of the blocking `WalletsAPI` struct
+    #[cfg(feature = "server")]
+    pub fn wallets(&self) -> WalletsAPI {
+        WalletsAPI {
+            inner: self.inner.wallets(),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// The `payment_links` method returns an instance of the blocking `PaymentLinksAPI` struct
+    #[cfg(feature = "server")]
+    pub fn payment_links(&self) -> PaymentLinksAPI {
+        PaymentLinksAPI {
+            inner: self.inner.payment_links(),
+            runtime: self.runtime.clone(),
+        }
+    }
+}
+
+/// Wraps an existing async client, e.g. one that was already configured elsewhere.
+///
+/// # Panics
+///
+/// Panics if the internal runtime cannot be created.
+impl From<crate::Intasend> for Intasend {
+    fn from(inner: crate::Intasend) -> Self {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("[!] Failed to build the blocking runtime");
+
+        Self {
+            inner,
+            runtime: Arc::new(runtime),
+        }
+    }
+}
+
+/// The blocking counterpart of [`crate::CollectionsAPI`].
+#[derive(Debug)]
+pub struct CollectionsAPI {
+    inner: crate::CollectionsAPI,
+    runtime: Arc<Runtime>,
+}
+
+impl CollectionsAPI {
+    /// The `mpesa_stk_push` method initiates an M-pesa transaction to trigger an authorization
+    /// from the end user to accept the transaction.
+    pub fn mpesa_stk_push(&self, payload: MpesaStkPushRequest) -> Result<MpesaStkPushResponse> {
+        self.runtime.block_on(self.inner.mpesa_stk_push(payload))
+    }
+
+    /// The `status` method initiates an M-pesa query about an initiated transaction
+    /// authorised from the end user.
+    pub fn status(&self, payload: StkPushStatusRequest) -> Result<StkPushStatusResponse> {
+        self.runtime.block_on(self.inner.status(payload))
+    }
+}
+
+/// The blocking counterpart of [`crate::CheckoutsAPI`].
+#[derive(Debug)]
+pub struct CheckoutsAPI {
+    inner: crate::CheckoutsAPI,
+    runtime: Arc<Runtime>,
+}
+
+impl CheckoutsAPI {
+    /// The `initiate` method generates a checkout link that you'll send to your customers to complete payment
+    pub fn initiate(&self, payload: CheckoutRequest) -> Result<CheckoutResponse> {
+        self.runtime.block_on(self.inner.initiate(payload))
+    }
+
+    /// The `details` method gets the details of an initiated checkout
+    pub fn details(&self, payload: CheckoutDetailsRequest) -> Result<CheckoutDetailsResponse> {
+        self.runtime.block_on(self.inner.details(payload))
+    }
+
+    /// The `initiate_and_store` method initiates a checkout and saves its id and signature in `store`
+    pub fn initiate_and_store<S: CheckoutStore + ?Sized>(
+        &self,
+        payload: CheckoutRequest,
+        store: &S,
+    ) -> Result<CheckoutResponse> {
+        self.runtime
+            .block_on(self.inner.initiate_and_store(payload, store))
+    }
+
+    /// The `details_by_id` method gets the details of a checkout saved in `store`
+    pub fn details_by_id<S: CheckoutStore + ?Sized>(
+        &self,
+        checkout_id: &str,
+        store: &S,
+    ) -> Result<CheckoutDetailsResponse> {
+        self.runtime
+            .block_on(self.inner.details_by_id(checkout_id, store))
+    }
+
+    /// The `wait_until_paid` method waits for a checkout to be paid, returning its paid details
+    pub fn wait_until_paid(
+        &self,
+        checkout_id: &str,
+        signature: &str,
+        options: CheckoutWaitOptions,
+    ) -> Result<CheckoutDetailsResponse> {
+        self.runtime
+            .block_on(self.inner.wait_until_paid(checkout_id, signature, options))
+    }
+}
+
+/// The blocking counterpart of [`crate::PayoutsAPI`].
+#[derive(Debug)]
+pub struct PayoutsAPI {
+    inner: crate::PayoutsAPI,
+    runtime: Arc<Runtime>,
+}
+
+impl PayoutsAPI {
+    /// The `initiate` method initiates the API requests to IntaSend's `Payouts` (Send Money) API.
+    pub fn initiate(&self, payload: PayoutRequest) -> Result<Payout> {
+        self.runtime.block_on(self.inner.initiate(payload))
+    }
+
+    /// The `approve_with_policy` method approves a payout if `policy` allows it, returning the
+    /// decision record.
+    pub fn approve_with_policy(
+        &self,
+        pending: &PendingPayout,
+        policy: &PayoutApprovalPolicy,
+        now: SystemTime,
+    ) -> Result<(ApprovalRecord, Option<Payout>)> {
+        self.runtime
+            .block_on(self.inner.approve_with_policy(pending, policy, now))
+    }
+
+    /// The `initiate_batches` method initiates the batches of a `PayoutBatchPlanner`, at most
+    /// `concurrency` at a time.
+    pub fn initiate_batches(
+        &self,
+        batches: Vec<PayoutRequest>,
+        concurrency: usize,
+    ) -> PayoutBatchReport {
+        self.runtime
+            .block_on(self.inner.initiate_batches(batches, concurrency))
+    }
+
+    /// The `mpesa_b2c` method initates Mpesa B2C payout API requests to IntaSend's `Payouts` API.
+    pub fn mpesa_b2c(&self, payload: PayoutRequest) -> Result<Payout> {
+        self.runtime.block_on(self.inner.mpesa_b2c(payload))
+    }
+
+    /// The `mpesa_b2b` method initates Mpesa B2B payout API requests to IntaSend's `Payouts` API.
+    pub fn mpesa_b2b(&self, payload: PayoutRequest) -> Result<Payout> {
+        self.runtime.block_on(self.inner.mpesa_b2b(payload))
+    }
+
+    /// The `bank` method initates bank payout API requests to IntaSend's `Payouts` API.
+    pub fn bank(&self, payload: PayoutRequest) -> Result<Payout> {
+        self.runtime.block_on(self.inner.bank(payload))
+    }
+
+    /// The `intasend` method initiates intasend payout API requests to IntaSend's `Payouts` API.
+    pub fn intasend(&self, payload: PayoutRequest) -> Result<Payout> {
+        self.runtime.block_on(self.inner.intasend(payload))
+    }
+
+    /// The `airtime` method initiates airtime payout API requests to IntaSend's `Payouts` API.
+    pub fn airtime(&self, payload: PayoutRequest) -> Result<Payout> {
+        self.runtime.block_on(self.inner.airtime(payload))
+    }
+
+    /// The `approve` method approves the initiated payout API requests to IntaSend's `Payouts` API.
+    pub fn approve(&self, payload: PayoutApprovalRequest) -> Result<Payout> {
+        self.runtime.block_on(self.inner.approve(payload))
+    }
+
+    /// The `status` method checks the status of the initiated payout API requests to IntaSend's `Payouts` API.
+    pub fn status(&self, payload: PayoutStatusRequest) -> Result<Payout> {
+        self.runtime.block_on(self.inner.status(payload))
+    }
+
+    /// The `cancel` method cancels the initiated payout API requests to IntaSend's `Payouts` API.
+    pub fn cancel(&self, payload: PayoutCancelRequest) -> Result<Payout> {
+        self.runtime.block_on(self.inner.cancel(payload))
+    }
+
+    /// The `bank_codes_ke` method gets all bank codes from the IntaSend's API.
+    pub fn bank_codes_ke(&self) -> Result<Vec<BankCodes>> {
+        self.runtime.block_on(self.inner.bank_codes_ke())
+    }
+}
+
+/// The blocking counterpart of [`crate::RefundsAPI`].
+#[derive(Debug)]
+pub struct RefundsAPI {
+    inner: crate::RefundsAPI,
+    runtime: Arc<Runtime>,
+}
+
+impl RefundsAPI {
+    /// The `list` method returns all the refunds made by an entity from the IntaSend API
+    pub fn list(&self) -> Result<RefundListResponse> {
+        self.runtime.block_on(self.inner.list())
+    }
+
+    /// The `create` method creates a new refund for a transaction whose status is **COMPLETE**.
+    ///
+    /// `create` is not deduplicated, use `create_with_idempotency_key` for the refunds that
+    /// may be retried.
+    pub fn create(&self, payload: RefundRequest) -> Result<Refund> {
+        self.runtime.block_on(self.inner.create(payload))
+    }
+
+    /// The `create_with_idempotency_key` method creates a new refund like `create`, under
+    /// `idempotency_key`.
+    pub fn create_with_idempotency_key(
+        &self,
+        payload: RefundRequest,
+        idempotency_key: String,
+    ) -> Result<Refund> {
+        self.runtime.block_on(
+            self.inner
+                .create_with_idempotency_key(payload, idempotency_key),
+        )
+    }
+
+    /// The `get` method returns the specific refund with the specified `chargeback_id`.
+    pub fn get(&self, chargeback_id: String) -> Result<Refund> {
+        self.runtime.block_on(self.inner.get(chargeback_id))
+    }
+}
+
+/// The blocking counterpart of [`crate::WalletsAPI`].
+#[derive(Debug)]
+pub struct WalletsAPI {
+    inner: crate::WalletsAPI,
+    runtime: Arc<Runtime>,
+}
+
+impl WalletsAPI {
+    /// The `list` method lists the wallets owned by you or created in your account.
+    pub fn list(&self) -> Result<WalletListResponse> {
+        self.runtime.block_on(self.inner.list())
+    }
+
+    /// The `details` method gets a wallet's details.
+    pub fn details(&self, wallet_id: String) -> Result<Wallet> {
+        self.runtime.block_on(self.inner.details(wallet_id))
+    }
+
+    /// The `create` method creates a new **WORKING** wallet.
+    pub fn create(&self, payload: WalletCreateDetails) -> Result<Wallet> {
+        self.runtime.block_on(self.inner.create(payload))
+    }
+
+    /// The `transactions` method gets all the transactions from a specific Wallet.
+    pub fn transactions(&self, wallet_id: String) -> Result<WalletTransactionsResponse> {
+        self.runtime.block_on(self.inner.transactions(wallet_id))
+    }
+
+    /// The `intra_transfer` method sends funds from one IntaSend wallet to another.
+    pub fn intra_transfer(
+        &self,
+        source_wallet_id: String,
+        payload: WalletIntraTransferRequest,
+    ) -> Result<WalletIntraTransferResponse> {
+        self.runtime
+            .block_on(self.inner.intra_transfer(source_wallet_id, payload))
+    }
+
+    /// The `fund_mpesa` method funds a specific IntaSend Wallet using M-pesa.
+    pub fn fund_mpesa(&self, payload: FundMpesaRequest) -> Result<FundMpesaResponse> {
+        self.runtime.block_on(self.inner.fund_mpesa(payload))
+    }
+
+    /// The `fund_checkout` method funds a specific IntaSend Wallet using checkout links.
+    pub fn fund_checkout(&self, payload: FundCheckoutRequest) -> Result<FundCheckoutResponse> {
+        self.runtime.block_on(self.inner.fund_checkout(payload))
+    }
+}
+
+/// The blocking counterpart of [`crate::PaymentLinksAPI`].
+#[derive(Debug)]
+pub struct PaymentLinksAPI {
+    inner: crate::PaymentLinksAPI,
+    runtime: Arc<Runtime>,
+}
+
+impl PaymentLinksAPI {
+    /// The `list` method lists all payment links created.
+    pub fn list(&self) -> Result<PaymentLinksListResponse> {
+        self.runtime.block_on(self.inner.list())
+    }
+
+    /// The `details` method gets a payment link's details.
+    pub fn details(&self, payment_link_id: String) -> Result<PaymentLink> {
+        self.runtime.block_on(self.inner.details(payment_link_id))
+    }
+
+    /// The `create` method creates a payment link.
+    pub fn create(&self, payload: PaymentLinksCreateDetails) -> Result<PaymentLink> {
+        self.runtime.block_on(self.inner.create(payload))
+    }
+
+    /// The `update` method updates the details of a payment link.
+    pub fn update(
+        &self,
+        payment_link_id: String,
+        payload: PaymentLinksUpdateDetails,
+    ) -> Result<PaymentLink> {
+        self.runtime
+            .block_on(self.inner.update(payment_link_id, payload))
+    }
+
+    /// The `activate` method enables a payment link.
+    pub fn activate(&self, payment_link_id: String) -> Result<PaymentLink> {
+        self.runtime.block_on(self.inner.activate(payment_link_id))
+    }
+
+    /// The `deactivate` method disables a payment link, without deleting it.
+    pub fn deactivate(&self, payment_link_id: String) -> Result<PaymentLink> {
+        self.runtime
+            .block_on(self.inner.deactivate(payment_link_id))
+    }
+
+    /// The `delete` method deletes a payment link.
+    pub fn delete(&self, payment_link_id: String) -> Result<()> {
+        self.runtime.block_on(self.inner.delete(payment_link_id))
+    }
+
+    /// The `create_many` method creates payment links, at most `concurrency` at a time,
+    /// returning the result of each link.
+    pub fn create_many<I>(&self, payloads: I, concurrency: usize) -> Vec<Result<PaymentLink>>
+    where
+        I: IntoIterator<Item = PaymentLinksCreateDetails>,
+    {
+        self.runtime
+            .block_on(self.inner.create_many(payloads, concurrency))
+    }
+
+    /// The `analytics` method computes the usage of a payment link from the transactions of
+    /// its wallet.
+    pub fn analytics(&self, link: &PaymentLink, wallet_id: String) -> Result<PaymentLinkAnalytics> {
+        self.runtime.block_on(self.inner.analytics(link, wallet_id))
+    }
+
+    /// The `analytics_many` method computes the usage of several payment links collecting
+    /// into the same wallet.
+    pub fn analytics_many(
+        &self,
+        links: &[PaymentLink],
+        wallet_id: String,
+    ) -> Result<Vec<PaymentLinkAnalytics>> {
+        self.runtime
+            .block_on(self.inner.analytics_many(links, wallet_id))
+    }
+}
@@ -0,0 +1,182 @@
+//! Server-side rendering of the IntaSend inline checkout button.
+
+use anyhow::Error;
+use serde_json::Value as JSON;
+
+use super::checkout::{CheckoutRequest, CheckoutsAPI};
+
+/// The IntaSend inline checkout SDK loaded by the rendered snippets
+pub const INLINE_SDK_URL: &str =
+    "https://unpkg.com/intasend-inlinejs-sdk@4.0.7/build/intasend-inline.js";
+
+/// `CheckoutButton` struct - renders the HTML snippet of an IntaSend inline checkout button
+///
+/// The fields of the `CheckoutRequest` become the `data-*` attributes of the button, every
+/// value being HTML-escaped, so customer input can be rendered safely.
+///
+/// ```rust
+/// let checkout_req = intasend::CheckoutRequest::builder(
+///     rust_decimal::Decimal::new(10000, 2),
+///     intasend::Currency::Kes,
+/// )
+/// .customer("Joe", "Doe", "joe@doe.com")
+/// .api_ref("order-1024")
+/// .build();
+///
+/// let html = intasend::CheckoutButton::new("ISPubKey_test_...", false, checkout_req)
+///     .label("Pay order #1024")
+///     .render();
+/// assert!(html.contains(r#"data-api_ref="order-1024""#));
+/// ```
+#[derive(Clone, Debug)]
+pub struct CheckoutButton {
+    publishable_key: String,
+    live: bool,
+    request: CheckoutRequest,
+    label: String,
+    script_url: String,
+}
+
+impl CheckoutButton {
+    /// Creates the button of `request`, paid to the account of `publishable_key`, `live`
+    /// being `false` for the sandbox
+    pub fn new(publishable_key: &str, live: bool, request: CheckoutRequest) -> Self {
+        Self {
+            publishable_key: publishable_key.to_string(),
+            live,
+            request,
+            label: "Pay Now".to_string(),
+            script_url: INLINE_SDK_URL.to_string(),
+        }
+    }
+
+    /// Sets the text of the button, `Pay Now` by default
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = label.to_string();
+        self
+    }
+
+    /// Sets the URL of the inline SDK, e.g. to pin another version or self-host it
+    pub fn script_url(mut self, script_url: &str) -> Self {
+        self.script_url = script_url.to_string();
+        self
+    }
+
+    /// Renders the button and the scripts initializing the inline SDK
+    pub fn render(&self) -> String {
+        let mut attributes = String::new();
+        if let Ok(JSON::Object(fields)) = serde_json::to_value(&self.request) {
+            for (name, value) in fields {
+                let value = match value {
+                    JSON::Null => continue,
+                    JSON::String(text) => text,
+                    other => other.to_string(),
+                };
+                attributes.push_str(&format!(
+                    " data-{}=\"{}\"",
+                    escape_html(&name),
+                    escape_html(&value)
+                ));
+            }
+        }
+
+        format!(
+            concat!(
+                "<button class=\"intaSendPayButton\"{}>{}</button>\n",
+                "<script src=\"{}\"></script>\n",
+                "<script>\n",
+                "  new window.IntaSend({{\n",
+                "    publicAPIKey: {},\n",
+                "    live: {}\n",
+                "  }});\n",
+                "</script>\n"
+            ),
+            attributes,
+            escape_html(&self.label),
+            escape_html(&self.script_url),
+            script_string(&self.publishable_key),
+            self.live
+        )
+    }
+}
+
+impl CheckoutsAPI {
+    /// The `button` method creates the inline checkout button of `payload` for the account of
+    /// the client, see `CheckoutButton`
+    pub fn button(&self, payload: CheckoutRequest) -> CheckoutButton {
+        CheckoutButton::new(
+            &self.intasend.publishable_key,
+            !self.intasend.test_mode,
+            payload,
+        )
+    }
+
+    /// The `redirect_url` method initiates a checkout and returns its signed URL, to redirect
+    /// the customer to the IntaSend hosted checkout page
+    pub async fn redirect_url(&self, payload: CheckoutRequest) -> Result<String, Error> {
+        Ok(self.initiate(payload).await?.url)
+    }
+}
+
+/// Escapes `text` for HTML text and double-quoted attributes.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+/// Encodes `text` as a JavaScript string literal that cannot close its `<script>` element.
+fn script_string(text: &str) -> String {
+    JSON::String(text.to_string())
+        .to_string()
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intasend::checkout::CheckoutMethod;
+    use crate::intasend::{Currency, Tarrif};
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn renders_the_checkout_fields() {
+        let request = CheckoutRequest::builder(Decimal::new(10000, 2), Currency::Kes)
+            .customer("Joe", "Doe", "joe@doe.com")
+            .phone_number("254712345678")
+            .method(CheckoutMethod::Mpesa)
+            .api_ref("order-1024")
+            .redirect_url("https://example.com/orders/1024?paid=1&ref=x")
+            .tarrif(Tarrif::CustomerPays)
+            .build();
+
+        insta::assert_snapshot!(CheckoutButton::new("ISPubKey_test_1234", false, request)
+            .label("Pay order #1024")
+            .render());
+    }
+
+    #[test]
+    fn escapes_untrusted_values() {
+        let request = CheckoutRequest::builder(Decimal::new(5, 0), Currency::Usd)
+            .customer("\"><script>alert(1)</script>", "O'Brien", "joe@doe.com")
+            .api_ref("<img src=x onerror=alert(1)>")
+            .build();
+
+        insta::assert_snapshot!(
+            CheckoutButton::new("</script><script>alert(1)//", true, request)
+                .label("<b>Pay</b>")
+                .render()
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Persistence of the checkout sessions (id and signature) between their initiation and
+//! the lookup of their details.
+//!
+//! `CheckoutsAPI::initiate_and_store` saves a [`CheckoutSession`] in a [`CheckoutStore`],
+//! `CheckoutsAPI::details_by_id` looks its signature up again, so that only the checkout
+//! id has to be kept with your orders. The sessions expire with their signature,
+//! [`CheckoutStore::remove_expired`] cleans them up.
+//!
+//! `MemoryCheckoutStore` keeps the sessions in the process, `SqliteCheckoutStore` (`sqlite`
+//! feature) in a SQLite database.
+//!
+//! ```rust
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! use intasend::checkout_store::{CheckoutStore, MemoryCheckoutStore};
+//!
+//! let intasend = intasend::Intasend::new(
+//!     std::env::var("INTASEND_PUBLIC_KEY")?,
+//!     std::env::var("INTASEND_SECRET_KEY")?,
+//!     true,
+//! );
+//! let store = MemoryCheckoutStore::new();
+//!
+//! let checkout_req = intasend::CheckoutRequest::builder(
+//!     rust_decimal::Decimal::new(10000, 2),
+//!     intasend::Currency::Kes,
+//! )
+//! .api_ref("order-1024")
+//! .build();
+//! let checkout = intasend.checkout().initiate_and_store(checkout_req, &store).await?;
+//!
+//! // Later on, from the checkout id only
+//! let details = intasend.checkout().details_by_id(&checkout.id, &store).await?;
+//!
+//! // Periodically
+//! store.remove_expired(std::time::SystemTime::now())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::checkout::{CheckoutResponse, CheckoutSignature};
+use super::IntasendClientError;
+
+/// `CheckoutSession` struct - the stored id and signature of a checkout
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CheckoutSession {
+    pub checkout_id: String,
+    pub signature: String,
+    pub api_ref: Option<String>,
+    /// Expiry time of the signature, in seconds since the UNIX epoch, when it could be
+    /// decoded
+    pub expires_at: Option<u64>,
+}
+
+impl CheckoutSession {
+    /// Creates the session of a checkout, its expiry being read from its signature
+    pub fn new(checkout_id: &str, signature: &str, api_ref: Option<String>) -> Self {
+        Self {
+            checkout_id: checkout_id.to_string(),
+            signature: signature.to_string(),
+            api_ref,
+            expires_at: CheckoutSignature::decode(signature)
+                .ok()
+                .map(|claims| claims.expires_at),
+        }
+    }
+
+    /// Whether the signature of the session is expired at `time`
+    pub fn is_expired_at(&self, time: SystemTime) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| time >= UNIX_EPOCH + Duration::from_secs(expires_at))
+    }
+}
+
+impl From<&CheckoutResponse> for CheckoutSession {
+    fn from(response: &CheckoutResponse) -> Self {
+        Self::new(&response.id, &response.signature, None)
+    }
+}
+
+/// `CheckoutStore` trait - persists the checkout sessions
+///
+/// Implement it on top of your own database to keep the sessions with your orders.
+pub trait CheckoutStore: Debug + Send + Sync {
+    /// Saves `session`, replacing the session with the same checkout id.
+    fn save(&self, session: CheckoutSession) -> Result<(), IntasendClientError>;
+
+    /// Gets the session of `checkout_id`.
+    fn get(&self, checkout_id: &str) -> Result<Option<CheckoutSession>, IntasendClientError>;
+
+    /// Removes the session of `checkout_id`.
+    fn remove(&self, checkout_id: &str) -> Result<(), IntasendClientError>;
+
+    /// Removes the sessions expired at `time`, returning how many were removed.
+    fn remove_expired(&self, time: SystemTime) -> Result<usize, IntasendClientError>;
+}
+
+/// `MemoryCheckoutStore` struct - an in-process `CheckoutStore`
+#[derive(Debug, Default)]
+pub struct MemoryCheckoutStore {
+    sessions: Mutex<HashMap<String, CheckoutSession>>,
+}
+
+impl MemoryCheckoutStore {
+    /// Creates an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn sessions(&self) -> std::sync::MutexGuard<'_, HashMap<String, CheckoutSession>> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl CheckoutStore for MemoryCheckoutStore {
+    fn save(&self, session: CheckoutSession) -> Result<(), IntasendClientError> {
+        self.sessions().insert(session.checkout_id.clone(), session);
+        Ok(())
+    }
+
+    fn get(&self, checkout_id: &str) -> Result<Option<CheckoutSession>, IntasendClientError> {
+        Ok(self.sessions().get(checkout_id).cloned())
+    }
+
+    fn remove(&self, checkout_id: &str) -> Result<(), IntasendClientError> {
+        self.sessions().remove(checkout_id);
+        Ok(())
+    }
+
+    fn remove_expired(&self, time: SystemTime) -> Result<usize, IntasendClientError> {
+        let mut sessions = self.sessions();
+        let before = sessions.len();
+        sessions.retain(|_, session| !session.is_expired_at(time));
+        Ok(before - sessions.len())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteCheckoutStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use std::path::Path;
+    use std::sync::Mutex;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use rusqlite::{params, Connection, OptionalExtension};
+
+    use super::{CheckoutSession, CheckoutStore, IntasendClientError};
+
+    fn store_error(error: rusqlite::Error) -> IntasendClientError {
+        IntasendClientError::Store(error.into())
+    }
+
+    /// `SqliteCheckoutStore` struct - a `CheckoutStore` backed by a SQLite database
+    ///
+    /// The sessions are kept in an `intasend_checkout_sessions` table, created if needed.
+    #[derive(Debug)]
+    pub struct SqliteCheckoutStore {
+        connection: Mutex<Connection>,
+    }
+
+    impl SqliteCheckoutStore {
+        /// Opens (or creates) the database at `path`
+        pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, IntasendClientError> {
+            Self::with_connection(Connection::open(path).map_err(store_error)?)
+        }
+
+        /// Opens an in-memory database
+        pub fn open_in_memory() -> Result<Self, IntasendClientError> {
+            Self::with_connection(Connection::open_in_memory().map_err(store_error)?)
+        }
+
+        /// Uses `connection`, e.g. to share the database of your application
+        pub fn with_connection(connection: Connection) -> Result<Self, IntasendClientError> {
+            connection
+                .execute_batch(
+                    "CREATE TABLE IF NOT EXISTS intasend_checkout_sessions (
+                        checkout_id TEXT PRIMARY KEY NOT NULL,
+                        signature TEXT NOT NULL,
+                        api_ref TEXT,
+                        expires_at INTEGER
+                    );
+                    CREATE INDEX IF NOT EXISTS intasend_checkout_sessions_expires_at
+                        ON intasend_checkout_sessions (expires_at);",
+                )
+                .map_err(store_error)?;
+
+            Ok(Self {
+                connection: Mutex::new(connection),
+            })
+        }
+
+        fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+            self.connection
+                .lock()
+                .unwrap_or_else(|error| error.into_inner())
+        }
+    }
+
+    impl CheckoutStore for SqliteCheckoutStore {
+        fn save(&self, session: CheckoutSession) -> Result<(), IntasendClientError> {
+            self.connection()
+                .execute(
+                    "INSERT OR REPLACE INTO intasend_checkout_sessions
+                        (checkout_id, signature, api_ref, expires_at)
+                        VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        session.checkout_id,
+                        session.signature,
+                        session.api_ref,
+                        session.expires_at.map(|expires_at| expires_at as i64),
+                    ],
+                )
+                .map_err(store_error)?;
+            Ok(())
+        }
+
+        fn get(&self, checkout_id: &str) -> Result<Option<CheckoutSession>, IntasendClientError> {
+            self.connection()
+                .query_row(
+                    "SELECT checkout_id, signature, api_ref, expires_at
+                        FROM intasend_checkout_sessions WHERE checkout_id = ?1",
+                    params![checkout_id],
+                    |row| {
+                        Ok(CheckoutSession {
+                            checkout_id: row.get(0)?,
+                            signature: row.get(1)?,
+                            api_ref: row.get(2)?,
+                            expires_at: row
+                                .get::<_, Option<i64>>(3)?
+                                .map(|expires_at| expires_at as u64),
+                        })
+                    },
+                )
+                .optional()
+                .map_err(store_error)
+        }
+
+        fn remove(&self, checkout_id: &str) -> Result<(), IntasendClientError> {
+            self.connection()
+                .execute(
+                    "DELETE FROM intasend_checkout_sessions WHERE checkout_id = ?1",
+                    params![checkout_id],
+                )
+                .map_err(store_error)?;
+            Ok(())
+        }
+
+        fn remove_expired(&self, time: SystemTime) -> Result<usize, IntasendClientError> {
+            let now = time
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            self.connection()
+                .execute(
+                    "DELETE FROM intasend_checkout_sessions WHERE expires_at <= ?1",
+                    params![now as i64],
+                )
+                .map_err(store_error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(checkout_id: &str, expires_at: u64) -> CheckoutSession {
+        CheckoutSession {
+            checkout_id: checkout_id.to_string(),
+            signature: format!("signature-{}", checkout_id),
+            api_ref: Some("order-1".to_string()),
+            expires_at: Some(expires_at),
+        }
+    }
+
+    fn expire_sessions(store: &dyn CheckoutStore) {
+        store.save(session("expired", 100)).unwrap();
+        store.save(session("valid", 300)).unwrap();
+        store
+            .save(CheckoutSession::new("undecodable", "mock.signature", None))
+            .unwrap();
+
+        let now = UNIX_EPOCH + Duration::from_secs(200);
+        assert_eq!(store.remove_expired(now).unwrap(), 1);
+        assert_eq!(store.get("expired").unwrap(), None);
+        assert_eq!(store.get("valid").unwrap(), Some(session("valid", 300)));
+        assert!(store.get("undecodable").unwrap().is_some());
+
+        store.remove("valid").unwrap();
+        assert_eq!(store.get("valid").unwrap(), None);
+    }
+
+    #[test]
+    fn memory_store_removes_expired_sessions() {
+        expire_sessions(&MemoryCheckoutStore::new());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_store_removes_expired_sessions() {
+        expire_sessions(&SqliteCheckoutStore::open_in_memory().unwrap());
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod mock_server_tests {
+    use super::*;
+    use crate::testing::MockServer;
+    use crate::{CheckoutRequest, Currency};
+    use rust_decimal_macros::dec;
+
+    #[tokio::test]
+    async fn stored_checkouts_are_looked_up_by_id() {
+        let server = MockServer::start();
+        let checkouts = server.client().checkout();
+        let store = MemoryCheckoutStore::new();
+
+        let request = CheckoutRequest::builder(dec!(100), Currency::Kes)
+            .api_ref("order-1")
+            .build();
+        let checkout = checkouts.initiate_and_store(request, &store).await.unwrap();
+
+        let details = checkouts.details_by_id(&checkout.id, &store).await.unwrap();
+        assert_eq!(details.api_ref.as_deref(), Some("order-1"));
+        assert!(checkouts.details_by_id("unknown", &store).await.is_err());
+    }
+}
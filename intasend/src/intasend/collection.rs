@@ -9,7 +9,8 @@ use serde_json::Value as JSON;
 
 use crate::Intasend;
 
-use super::{Customer, Invoice, RequestClient, RequestMethods, Tarrif};
+use super::idempotency::generate_reference;
+use super::{de, Customer, Invoice, RequestClient, RequestMethods, Tarrif};
 
 /// `Collection` struct implements methods for facilitating:
 /// Mpesa Express for merchant initiated online payments
@@ -77,21 +78,32 @@ impl CollectionsAPI {
     ///
     pub async fn mpesa_stk_push(
         &self,
-        payload: MpesaStkPushRequest,
+        mut payload: MpesaStkPushRequest,
     ) -> Result<MpesaStkPushResponse, Error> {
         let service_path: &str = "/api/v1/payment/mpesa-stk-push/";
         let request_method: RequestMethods = RequestMethods::Post;
 
-        let mpesa_stk_push_response = &self
-            .intasend
-            .send::<MpesaStkPushRequest, MpesaStkPushResponse>(
-                Some(payload),
-                service_path,
-                request_method,
-            )
-            .await?;
+        let api_ref = payload
+            .api_ref
+            .get_or_insert_with(generate_reference)
+            .clone();
+
+        self.intasend
+            .idempotent(format!("mpesa-stk-push:{}", api_ref), || async {
+                let mpesa_stk_push_response = self
+                    .intasend
+                    .send::<MpesaStkPushRequest, MpesaStkPushResponse>(
+                        Some(payload),
+                        service_path,
+                        request_method,
+                    )
+                    .await?;
+                #[cfg(feature = "metrics")]
+                super::telemetry::record_stk_push_initiated();
 
-        Ok(mpesa_stk_push_response.clone())
+                Ok(mpesa_stk_push_response)
+            })
+            .await
     }
 
     /// The `status` method initiates an M-pesa query about an initiated transaction
@@ -166,17 +178,19 @@ impl CollectionsAPI {
                 request_method,
             )
             .await?;
-        // println!("Json Response: {:#?}", json_response);
-
-        // let status_response = StkPushStatusResponse::from_value(&json_response).unwrap();
-        // println!("Status Response: {:#?}", status_response);
+        #[cfg(feature = "metrics")]
+        if let Some(invoice) = &status_response.invoice {
+            self.intasend
+                .stk_push_outcomes
+                .record(&invoice.invoice_id, &invoice.state);
+        }
 
         Ok(status_response.clone())
     }
 }
 
 /// `MPesaSTKPushRequest` Struct - `CollectionsAPI`
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct MpesaStkPushRequest {
     pub amount: Decimal,
     pub phone_number: String,
@@ -190,13 +204,14 @@ pub struct MpesaStkPushResponse {
     pub invoice: Option<Invoice>,
     pub customer: Option<Customer>,
     pub payment_link: Option<String>,
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub refundable: bool,
     pub created_at: String,
     pub updated_at: String,
 }
 
 /// `StkPushStatusRequest` Struct - `CollectionsAPI`
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct StkPushStatusRequest {
     pub invoice_id: String,
     pub checkout_id: Option<String>,
@@ -224,9 +239,11 @@ pub struct Meta {
 pub struct PaymentLink {
     pub id: String,
     pub title: String,
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub is_active: bool,
     pub redirect_url: Option<String>,
     pub amount: Decimal,
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub usage_limit: Decimal,
     pub qrcode_file: Option<String>,
     pub url: String,
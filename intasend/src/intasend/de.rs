@@ -0,0 +1,171 @@
+//! Lenient deserializers for the fields whose encoding varies in the IntaSend payloads.
+//!
+//! Depending on the endpoint and the state of the resource, IntaSend returns `null` for
+//! fields documented as always present, numbers as decimal strings (`"3.00"`) and
+//! pagination cursors as URLs. The models use these with `#[serde(default,
+//! deserialize_with = "...")]` so that missing fields are tolerated as well.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value as JSON;
+
+/// Deserializes `null` as the default value of the field.
+pub(crate) fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Deserializes a number given as a JSON number or string, e.g. `3`, `3.0`, `"3"` or
+/// `"3.00"`, `null` and empty strings being the default value of the field.
+pub(crate) fn lenient_number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + FromStr,
+    T::Err: Display,
+{
+    let text = match JSON::deserialize(deserializer)? {
+        JSON::Null => return Ok(T::default()),
+        JSON::Number(number) => number.to_string(),
+        JSON::String(text) => text,
+        other => {
+            return Err(D::Error::custom(format!(
+                "expected a number, found {}",
+                other
+            )))
+        }
+    };
+    parse_number(text.trim()).map_err(D::Error::custom)
+}
+
+/// Deserializes an optional string given as a JSON string or number, e.g. the `next`
+/// cursor of the paginated responses, which is either a page number or a URL.
+pub(crate) fn lenient_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match JSON::deserialize(deserializer)? {
+        JSON::Null => Ok(None),
+        JSON::String(text) => Ok(Some(text)),
+        JSON::Number(number) => Ok(Some(number.to_string())),
+        other => Err(D::Error::custom(format!(
+            "expected a string, found {}",
+            other
+        ))),
+    }
+}
+
+/// Parses `text`, dropping a zero fractional part (`"3.00"`) for the integer types.
+fn parse_number<T>(text: &str) -> Result<T, T::Err>
+where
+    T: Default + FromStr,
+{
+    if text.is_empty() {
+        return Ok(T::default());
+    }
+    text.parse().or_else(|error| match text.split_once('.') {
+        Some((integer, fraction)) if fraction.bytes().all(|b| b == b'0') => integer.parse(),
+        _ => Err(error),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::collection::MpesaStkPushResponse;
+    use super::super::fixtures::samples;
+    use super::super::payment_links::PaymentLinksListResponse;
+    use super::super::wallets::WalletListResponse;
+    use super::*;
+    use proptest::prelude::*;
+    use rust_decimal::Decimal;
+    use serde_json::json;
+
+    /// The encodings of `value` found in the IntaSend payloads.
+    fn encoded_number(value: u32) -> impl Strategy<Value = JSON> {
+        prop_oneof![
+            Just(json!(value)),
+            Just(json!(value as f64)),
+            Just(json!(value.to_string())),
+            Just(json!(format!("{}.00", value))),
+        ]
+    }
+
+    fn nullable(value: JSON) -> impl Strategy<Value = JSON> {
+        prop_oneof![Just(value), Just(JSON::Null)]
+    }
+
+    #[test]
+    fn pagination_cursors_may_be_urls() {
+        let mut payload: JSON = serde_json::from_str(samples::PAYMENT_LINKS_LIST_RESPONSE).unwrap();
+        payload["next"] = json!("https://sandbox.intasend.com/api/v1/paymentlinks/?page=2");
+        payload["previous"] = json!(1);
+
+        let response: PaymentLinksListResponse = serde_json::from_value(payload).unwrap();
+        assert_eq!(
+            response.next.as_deref(),
+            Some("https://sandbox.intasend.com/api/v1/paymentlinks/?page=2")
+        );
+        assert_eq!(response.previous.as_deref(), Some("1"));
+    }
+
+    proptest! {
+        #[test]
+        fn stk_push_responses_parse(
+            retry_count in 0..10u32,
+            charges in 0..1000u32,
+            refundable in nullable(json!(true)),
+            card_info in nullable(json!({"bin_country": "KE", "card_type": "VISA"})),
+            host in nullable(json!("https://example.com")),
+            encoding in 0..4usize,
+        ) {
+            let mut payload: JSON = serde_json::from_str(samples::MPESA_STK_PUSH_RESPONSE).unwrap();
+            payload["invoice"]["retry_count"] = [
+                json!(retry_count),
+                json!(retry_count.to_string()),
+                json!(format!("{}.0", retry_count)),
+                json!(retry_count as f64),
+            ][encoding].clone();
+            payload["invoice"]["charges"] = json!(charges);
+            payload["invoice"]["card_info"] = card_info.clone();
+            payload["invoice"]["host"] = host;
+            payload["refundable"] = refundable.clone();
+
+            let response: MpesaStkPushResponse = serde_json::from_value(payload).unwrap();
+            let invoice = response.invoice.unwrap();
+            prop_assert_eq!(invoice.retry_count, retry_count);
+            prop_assert_eq!(invoice.charges, Decimal::from(charges));
+            prop_assert_eq!(invoice.card_info.card_type.is_some(), !card_info.is_null());
+            prop_assert_eq!(response.refundable, !refundable.is_null());
+        }
+
+        #[test]
+        fn paginated_responses_parse(
+            count in (0..500u32).prop_flat_map(encoded_number),
+            amount in (1..100_000u32).prop_flat_map(encoded_number),
+            usage_limit in (0..100u32).prop_flat_map(encoded_number),
+            is_active in nullable(json!(true)),
+            next in prop_oneof![Just(JSON::Null), Just(json!(2)), Just(json!("https://sandbox.intasend.com/api/v1/wallets/?page=2"))],
+        ) {
+            let mut links: JSON = serde_json::from_str(samples::PAYMENT_LINKS_LIST_RESPONSE).unwrap();
+            links["count"] = count.clone();
+            links["next"] = next.clone();
+            links["results"][0]["amount"] = amount;
+            links["results"][0]["usage_limit"] = usage_limit;
+            links["results"][0]["is_active"] = is_active;
+            let links: Result<PaymentLinksListResponse, _> = serde_json::from_value(links);
+            prop_assert!(links.is_ok(), "{:?}", links.err());
+
+            let mut wallets: JSON = serde_json::from_str(samples::WALLET_LIST_RESPONSE).unwrap();
+            wallets["count"] = count;
+            wallets["next"] = next;
+            wallets["results"][0]["can_disburse"] = JSON::Null;
+            let wallets: Result<WalletListResponse, _> = serde_json::from_value(wallets);
+            prop_assert!(wallets.is_ok(), "{:?}", wallets.err());
+        }
+    }
+}
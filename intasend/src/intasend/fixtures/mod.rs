@@ -0,0 +1,838 @@
+//! Builders of the IntaSend response models, and of the payout and payment link requests,
+//! for the tests of the SDK users.
+//!
+//! Every builder starts from a consistent model (an invoice's charges and net amount
+//! follow its value, a refund's amount follows its transaction...) whose fields can be
+//! overridden one by one. The remaining fields of the built models are public, struct
+//! update syntax covers them.
+//!
+//! The [`samples`] module holds realistic JSON payloads of every response type, e.g. to
+//! stub an HTTP client or to check the deserialization of custom models.
+//!
+//! ```rust
+//! use intasend::fixtures::{InvoiceBuilder, TransactionBuilder, WalletBuilder};
+//! use intasend::{TransactionType, WalletType};
+//! use rust_decimal::Decimal;
+//!
+//! let invoice = InvoiceBuilder::new()
+//!     .value(Decimal::new(1000, 0))
+//!     .complete("SA27F4TQ2B")
+//!     .build();
+//! assert_eq!(invoice.net_amount, Decimal::new(970, 0));
+//!
+//! let sale = TransactionBuilder::sale(invoice).build();
+//! assert!(matches!(sale.trans_type, TransactionType::Sale));
+//!
+//! let wallet = intasend::Wallet {
+//!     label: "Payroll".to_string(),
+//!     ..WalletBuilder::new().wallet_type(WalletType::Working).build()
+//! };
+//! ```
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use super::checkout::{
+    CheckoutDefaults, CheckoutDetailsResponse, CheckoutMethod, CheckoutSignature,
+};
+use super::payment_links::PaymentLinksCreateDetails;
+use super::payouts::{PayoutApproval, PayoutRequest, PayoutRequestTransaction};
+use super::refunds::{Refund, RefundReason};
+use super::wallets::{Wallet, WalletType};
+use super::{
+    CardInfo, Currency, Customer, Invoice, PayoutProvider, Tarrif, Transaction, TransactionStatus,
+    TransactionType,
+};
+
+/// The timestamp of the models built with the default values
+pub const CREATED_AT: &str = "2024-01-02T10:10:02.111290+03:00";
+
+/// `CREATED_AT`, in seconds since the UNIX epoch
+const CREATED_AT_SECONDS: u64 = 1_704_179_402;
+
+/// The collection fee rate applied to the default invoice charges
+const COLLECTION_RATE: Decimal = dec!(0.03);
+
+/// Realistic JSON payloads of the IntaSend API responses, one per response type.
+pub mod samples {
+    /// `MpesaStkPushResponse` of a pending STK push
+    pub const MPESA_STK_PUSH_RESPONSE: &str = include_str!("samples/mpesa_stk_push_response.json");
+    /// `StkPushStatusResponse` of a completed STK push
+    pub const STK_PUSH_STATUS_RESPONSE: &str =
+        include_str!("samples/stk_push_status_response.json");
+    /// `CheckoutResponse` of a card checkout
+    pub const CHECKOUT_RESPONSE: &str = include_str!("samples/checkout_response.json");
+    /// `CheckoutDetailsResponse` of an unpaid card checkout
+    pub const CHECKOUT_DETAILS_RESPONSE: &str =
+        include_str!("samples/checkout_details_response.json");
+    /// `Payout` awaiting approval
+    pub const PAYOUT: &str = include_str!("samples/payout.json");
+    /// List of `BankCodes`
+    pub const BANK_CODES: &str = include_str!("samples/bank_codes.json");
+    /// Pending `Refund`
+    pub const REFUND: &str = include_str!("samples/refund.json");
+    /// `RefundListResponse` of a single refund
+    pub const REFUND_LIST_RESPONSE: &str = include_str!("samples/refund_list_response.json");
+    /// Settlement `Wallet`
+    pub const WALLET: &str = include_str!("samples/wallet.json");
+    /// `WalletListResponse` of a settlement and a working wallet
+    pub const WALLET_LIST_RESPONSE: &str = include_str!("samples/wallet_list_response.json");
+    /// `WalletTransactionsResponse` of a sale and a payout
+    pub const WALLET_TRANSACTIONS_RESPONSE: &str =
+        include_str!("samples/wallet_transactions_response.json");
+    /// `WalletIntraTransferResponse`
+    pub const WALLET_INTRA_TRANSFER_RESPONSE: &str =
+        include_str!("samples/wallet_intra_transfer_response.json");
+    /// `FundMpesaResponse` of a pending STK push
+    pub const FUND_MPESA_RESPONSE: &str = include_str!("samples/fund_mpesa_response.json");
+    /// `FundCheckoutResponse` of an M-Pesa checkout
+    pub const FUND_CHECKOUT_RESPONSE: &str = include_str!("samples/fund_checkout_response.json");
+    /// `PaymentLink`
+    pub const PAYMENT_LINK: &str = include_str!("samples/payment_link.json");
+    /// `PaymentLinksListResponse` of a single link
+    pub const PAYMENT_LINKS_LIST_RESPONSE: &str =
+        include_str!("samples/payment_links_list_response.json");
+    /// `IntasendApiError` of a rejected request
+    pub const API_ERROR: &str = include_str!("samples/api_error.json");
+}
+
+/// A customer paying with M-Pesa
+pub fn customer() -> Customer {
+    Customer {
+        customer_id: "KR6GW4Q".to_string(),
+        phone_number: "254712345678".to_string(),
+        email: None,
+        first_name: None,
+        last_name: None,
+        country: Some("KE".to_string()),
+        zipcode: None,
+        provider: "M-PESA".to_string(),
+        created_at: CREATED_AT.to_string(),
+        updated_at: CREATED_AT.to_string(),
+    }
+}
+
+/// `InvoiceBuilder` struct - builds a pending M-Pesa `Invoice` of KES 10 by default
+#[derive(Clone, Debug)]
+pub struct InvoiceBuilder {
+    invoice: Invoice,
+    charges: Option<Decimal>,
+}
+
+impl Default for InvoiceBuilder {
+    fn default() -> Self {
+        Self {
+            invoice: Invoice {
+                invoice_id: "RXX5P8R".to_string(),
+                state: "PENDING".to_string(),
+                provider: "M-PESA".to_string(),
+                charges: Decimal::ZERO,
+                net_amount: Decimal::ZERO,
+                currency: "KES".to_string(),
+                value: dec!(10.00),
+                account: "254712345678".to_string(),
+                api_ref: Some("order-1".to_string()),
+                mpesa_reference: None,
+                host: "https://example.com".to_string(),
+                card_info: CardInfo {
+                    bin_country: None,
+                    card_type: None,
+                },
+                retry_count: 0,
+                failed_reason: None,
+                failed_code: None,
+                failed_code_link: None,
+                created_at: CREATED_AT.to_string(),
+                updated_at: CREATED_AT.to_string(),
+            },
+            charges: None,
+        }
+    }
+}
+
+impl InvoiceBuilder {
+    /// Creates the builder of the default invoice
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn invoice_id(mut self, invoice_id: &str) -> Self {
+        self.invoice.invoice_id = invoice_id.to_string();
+        self
+    }
+
+    /// Sets the state: `PENDING`, `PROCESSING`, `COMPLETE` or `FAILED`
+    pub fn state(mut self, state: &str) -> Self {
+        self.invoice.state = state.to_string();
+        self
+    }
+
+    /// Completes the invoice with the M-Pesa receipt `mpesa_reference`
+    pub fn complete(mut self, mpesa_reference: &str) -> Self {
+        self.invoice.state = "COMPLETE".to_string();
+        self.invoice.mpesa_reference = Some(mpesa_reference.to_string());
+        self
+    }
+
+    /// Fails the invoice with the provider's `code` and `reason`
+    pub fn failed(mut self, code: &str, reason: &str) -> Self {
+        self.invoice.state = "FAILED".to_string();
+        self.invoice.failed_code = Some(code.to_string());
+        self.invoice.failed_reason = Some(reason.to_string());
+        self
+    }
+
+    /// Sets the provider, e.g. `CARD-PAYMENT`, and the paying account
+    pub fn provider(mut self, provider: &str, account: &str) -> Self {
+        self.invoice.provider = provider.to_string();
+        self.invoice.account = account.to_string();
+        self
+    }
+
+    pub fn currency(mut self, currency: &str) -> Self {
+        self.invoice.currency = currency.to_string();
+        self
+    }
+
+    /// Sets the value, the charges (3% unless set) and net amount follow it
+    pub fn value(mut self, value: Decimal) -> Self {
+        self.invoice.value = value;
+        self
+    }
+
+    pub fn charges(mut self, charges: Decimal) -> Self {
+        self.charges = Some(charges);
+        self
+    }
+
+    pub fn api_ref(mut self, api_ref: Option<&str>) -> Self {
+        self.invoice.api_ref = api_ref.map(str::to_string);
+        self
+    }
+
+    pub fn build(self) -> Invoice {
+        let mut invoice = self.invoice;
+        invoice.charges = self
+            .charges
+            .unwrap_or_else(|| (invoice.value * COLLECTION_RATE).round_dp(2));
+        invoice.net_amount = invoice.value - invoice.charges;
+        invoice
+    }
+}
+
+/// `TransactionBuilder` struct - builds an available KES 9.70 sale `Transaction` by default
+#[derive(Clone, Debug)]
+pub struct TransactionBuilder {
+    transaction: Transaction,
+}
+
+impl Default for TransactionBuilder {
+    fn default() -> Self {
+        Self::sale(InvoiceBuilder::new().complete("SA27F4TQ2B").build())
+    }
+}
+
+impl TransactionBuilder {
+    /// Creates the builder of the default transaction
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the builder of the sale crediting the net amount of `invoice`
+    pub fn sale(invoice: Invoice) -> Self {
+        Self {
+            transaction: Transaction {
+                transaction_id: "0B6QMPY".to_string(),
+                currency: Currency::Kes,
+                value: invoice.net_amount,
+                running_balance: invoice.net_amount,
+                narrative: Some(format!("Payment {}", invoice.invoice_id)),
+                trans_type: TransactionType::Sale,
+                status: TransactionStatus::Available,
+                created_at: invoice.updated_at.clone(),
+                updated_at: invoice.updated_at.clone(),
+                invoice: Some(invoice),
+            },
+        }
+    }
+
+    /// Creates the builder of the payout debiting `amount`
+    pub fn payout(amount: Decimal) -> Self {
+        Self {
+            transaction: Transaction {
+                transaction_id: "7QJ2KXP".to_string(),
+                invoice: None,
+                currency: Currency::Kes,
+                value: -amount,
+                running_balance: Decimal::ZERO,
+                narrative: Some("Payout".to_string()),
+                trans_type: TransactionType::Payout,
+                status: TransactionStatus::Available,
+                created_at: CREATED_AT.to_string(),
+                updated_at: CREATED_AT.to_string(),
+            },
+        }
+    }
+
+    pub fn transaction_id(mut self, transaction_id: &str) -> Self {
+        self.transaction.transaction_id = transaction_id.to_string();
+        self
+    }
+
+    pub fn currency(mut self, currency: Currency) -> Self {
+        self.transaction.currency = currency;
+        self
+    }
+
+    pub fn value(mut self, value: Decimal) -> Self {
+        self.transaction.value = value;
+        self
+    }
+
+    pub fn running_balance(mut self, running_balance: Decimal) -> Self {
+        self.transaction.running_balance = running_balance;
+        self
+    }
+
+    pub fn narrative(mut self, narrative: Option<&str>) -> Self {
+        self.transaction.narrative = narrative.map(str::to_string);
+        self
+    }
+
+    pub fn trans_type(mut self, trans_type: TransactionType) -> Self {
+        self.transaction.trans_type = trans_type;
+        self
+    }
+
+    pub fn status(mut self, status: TransactionStatus) -> Self {
+        self.transaction.status = status;
+        self
+    }
+
+    pub fn build(self) -> Transaction {
+        self.transaction
+    }
+}
+
+/// `WalletBuilder` struct - builds the KES settlement `Wallet` by default
+#[derive(Clone, Debug)]
+pub struct WalletBuilder {
+    wallet: Wallet,
+}
+
+impl Default for WalletBuilder {
+    fn default() -> Self {
+        Self {
+            wallet: Wallet {
+                wallet_id: "ZQMMOQO".to_string(),
+                label: "default".to_string(),
+                can_disburse: true,
+                currency: Currency::Kes,
+                wallet_type: WalletType::Settlement,
+                current_balance: dec!(12500.00),
+                available_balance: dec!(12500.00),
+                updated_at: CREATED_AT.to_string(),
+            },
+        }
+    }
+}
+
+impl WalletBuilder {
+    /// Creates the builder of the default wallet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn wallet_id(mut self, wallet_id: &str) -> Self {
+        self.wallet.wallet_id = wallet_id.to_string();
+        self
+    }
+
+    pub fn label(mut self, label: &str) -> Self {
+        self.wallet.label = label.to_string();
+        self
+    }
+
+    pub fn currency(mut self, currency: Currency) -> Self {
+        self.wallet.currency = currency;
+        self
+    }
+
+    pub fn wallet_type(mut self, wallet_type: WalletType) -> Self {
+        self.wallet.wallet_type = wallet_type;
+        self
+    }
+
+    pub fn can_disburse(mut self, can_disburse: bool) -> Self {
+        self.wallet.can_disburse = can_disburse;
+        self
+    }
+
+    /// Sets both the current and available balances
+    pub fn balance(mut self, balance: Decimal) -> Self {
+        self.wallet.current_balance = balance;
+        self.wallet.available_balance = balance;
+        self
+    }
+
+    /// Sets the available balance only, e.g. when funds are clearing
+    pub fn available_balance(mut self, available_balance: Decimal) -> Self {
+        self.wallet.available_balance = available_balance;
+        self
+    }
+
+    pub fn build(self) -> Wallet {
+        self.wallet
+    }
+}
+
+/// `RefundBuilder` struct - builds a pending `Refund` of the default sale by default
+#[derive(Clone, Debug)]
+pub struct RefundBuilder {
+    refund: Refund,
+    amount: Option<Decimal>,
+}
+
+impl Default for RefundBuilder {
+    fn default() -> Self {
+        Self {
+            refund: Refund {
+                chargeback_id: "RL0QX1M".to_string(),
+                session_id: "3f2b7c1a-9d4e-4b8f-a6c5-2e1d0f9a8b7c".to_string(),
+                transaction: TransactionBuilder::new()
+                    .status(TransactionStatus::ChargebackPending)
+                    .build(),
+                amount: Decimal::ZERO,
+                status: "PENDING".to_string(),
+                reason: RefundReason::Other,
+                resolution: None,
+                staff_created: false,
+                created_at: CREATED_AT.to_string(),
+                updated_at: CREATED_AT.to_string(),
+            },
+            amount: None,
+        }
+    }
+}
+
+impl RefundBuilder {
+    /// Creates the builder of the default refund
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn chargeback_id(mut self, chargeback_id: &str) -> Self {
+        self.refund.chargeback_id = chargeback_id.to_string();
+        self
+    }
+
+    /// Sets the refunded transaction, the amount (unless set) follows its value
+    pub fn transaction(mut self, transaction: Transaction) -> Self {
+        self.refund.transaction = transaction;
+        self
+    }
+
+    pub fn amount(mut self, amount: Decimal) -> Self {
+        self.amount = Some(amount);
+        self
+    }
+
+    /// Sets the status, e.g. `PENDING`, `APPROVED`, `REJECTED`
+    pub fn status(mut self, status: &str) -> Self {
+        self.refund.status = status.to_string();
+        self
+    }
+
+    pub fn reason(mut self, reason: RefundReason) -> Self {
+        self.refund.reason = reason;
+        self
+    }
+
+    pub fn resolution(mut self, resolution: Option<&str>) -> Self {
+        self.refund.resolution = resolution.map(str::to_string);
+        self
+    }
+
+    pub fn build(self) -> Refund {
+        let mut refund = self.refund;
+        refund.amount = self
+            .amount
+            .unwrap_or_else(|| refund.transaction.value.abs());
+        refund
+    }
+}
+
+/// `CheckoutDetailsBuilder` struct - builds the `CheckoutDetailsResponse` of an unpaid
+/// KES 100 checkout by default
+#[derive(Clone, Debug)]
+pub struct CheckoutDetailsBuilder {
+    details: CheckoutDetailsResponse,
+    signature: Option<String>,
+    expires_at: u64,
+}
+
+impl Default for CheckoutDetailsBuilder {
+    fn default() -> Self {
+        let id = "9c5a6f07-79a6-4b2c-a0b7-4d38c2c2e5a1";
+        Self {
+            details: CheckoutDetailsResponse {
+                id: id.to_string(),
+                url: format!("https://sandbox.intasend.com/checkout/{}/express/", id),
+                signature: String::new(),
+                first_name: Some("Joe".to_string()),
+                last_name: Some("Doe".to_string()),
+                phone_number: None,
+                email: Some("joe@doe.com".to_string()),
+                country: None,
+                address: None,
+                city: None,
+                state: None,
+                zipcode: None,
+                api_ref: Some("order-1".to_string()),
+                wallet_id: None,
+                method: None,
+                channel: Some("API".to_string()),
+                host: Some("https://example.com".to_string()),
+                is_mobile: false,
+                version: None,
+                redirect_url: None,
+                amount: dec!(100.00),
+                currency: Some(Currency::Kes),
+                paid: false,
+                mobile_tarrif: Tarrif::BusinessPays,
+                card_tarrif: Tarrif::BusinessPays,
+                bitcoin_tarrif: Tarrif::BusinessPays,
+                ach_tarrif: Tarrif::BusinessPays,
+                created_at: CREATED_AT.to_string(),
+                updated_at: CREATED_AT.to_string(),
+                defaults: CheckoutDefaults {
+                    enable_card_payment: true,
+                    enable_mpesa_payment: true,
+                    enable_bitcoin_payment: false,
+                    enable_ach_payment: false,
+                    default_currency: Currency::Kes,
+                    default_tarrif: Tarrif::BusinessPays,
+                },
+            },
+            signature: None,
+            expires_at: CREATED_AT_SECONDS + 3600,
+        }
+    }
+}
+
+impl CheckoutDetailsBuilder {
+    /// Creates the builder of the default checkout details
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the checkout id, the URL follows it
+    pub fn id(mut self, id: &str) -> Self {
+        self.details.id = id.to_string();
+        self.details.url = format!("https://sandbox.intasend.com/checkout/{}/express/", id);
+        self
+    }
+
+    /// Sets the signature, a JWT issued for the checkout id by default
+    pub fn signature(mut self, signature: &str) -> Self {
+        self.signature = Some(signature.to_string());
+        self
+    }
+
+    /// Sets the expiry time of the default signature, in seconds since the UNIX epoch
+    pub fn expires_at(mut self, expires_at: u64) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+
+    pub fn amount(mut self, amount: Decimal, currency: Currency) -> Self {
+        self.details.amount = amount;
+        self.details.currency = Some(currency);
+        self
+    }
+
+    pub fn method(mut self, method: Option<CheckoutMethod>) -> Self {
+        self.details.method = method;
+        self
+    }
+
+    pub fn api_ref(mut self, api_ref: Option<&str>) -> Self {
+        self.details.api_ref = api_ref.map(str::to_string);
+        self
+    }
+
+    pub fn redirect_url(mut self, redirect_url: Option<&str>) -> Self {
+        self.details.redirect_url = redirect_url.map(str::to_string);
+        self
+    }
+
+    pub fn paid(mut self, paid: bool) -> Self {
+        self.details.paid = paid;
+        self
+    }
+
+    /// Sets the tarrif of every payment method
+    pub fn tarrif(mut self, tarrif: Tarrif) -> Self {
+        self.details.mobile_tarrif = tarrif.clone();
+        self.details.card_tarrif = tarrif.clone();
+        self.details.bitcoin_tarrif = tarrif.clone();
+        self.details.ach_tarrif = tarrif.clone();
+        self.details.defaults.default_tarrif = tarrif;
+        self
+    }
+
+    pub fn build(self) -> CheckoutDetailsResponse {
+        let mut details = self.details;
+        details.signature = self.signature.unwrap_or_else(|| {
+            CheckoutSignature {
+                scope: Some("express-checkout".to_string()),
+                issuer: Some("IntaSend Solutions Limited".to_string()),
+                audience: vec![details.id.clone()],
+                issued_at: CREATED_AT_SECONDS,
+                expires_at: self.expires_at,
+                account_id: Some("9QJ9KGY".to_string()),
+                reference: Some(details.id.clone()),
+            }
+            .encode()
+        });
+        details
+    }
+}
+
+/// A payout of `amount` to `account`, without beneficiary details
+pub fn payout_transaction(account: &str, amount: Decimal) -> PayoutRequestTransaction {
+    PayoutRequestTransaction {
+        name: None,
+        account: account.to_string(),
+        id_number: None,
+        amount,
+        bank_code: None,
+        category_name: None,
+        narrative: None,
+        account_type: None,
+        account_reference: None,
+    }
+}
+
+/// `PayoutRequestBuilder` struct - builds a KES M-Pesa B2C `PayoutRequest` without
+/// transactions by default
+#[derive(Clone, Debug)]
+pub struct PayoutRequestBuilder {
+    request: PayoutRequest,
+}
+
+impl Default for PayoutRequestBuilder {
+    fn default() -> Self {
+        Self {
+            request: PayoutRequest {
+                currency: Currency::Kes,
+                provider: Some(PayoutProvider::MpesaB2c),
+                device_id: None,
+                callback_url: None,
+                batch_reference: None,
+                requires_approval: None,
+                transactions: Vec::new(),
+            },
+        }
+    }
+}
+
+impl PayoutRequestBuilder {
+    /// Creates the builder of the default payout request
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn currency(mut self, currency: Currency) -> Self {
+        self.request.currency = currency;
+        self
+    }
+
+    pub fn provider(mut self, provider: Option<PayoutProvider>) -> Self {
+        self.request.provider = provider;
+        self
+    }
+
+    pub fn batch_reference(mut self, batch_reference: Option<&str>) -> Self {
+        self.request.batch_reference = batch_reference.map(str::to_string);
+        self
+    }
+
+    pub fn requires_approval(mut self, requires_approval: Option<PayoutApproval>) -> Self {
+        self.request.requires_approval = requires_approval;
+        self
+    }
+
+    /// Adds a payout of `amount` to `account`, see `payout_transaction`
+    pub fn transaction(mut self, account: &str, amount: Decimal) -> Self {
+        self.request
+            .transactions
+            .push(payout_transaction(account, amount));
+        self
+    }
+
+    /// Adds a payout of each amount, to the accounts `254712345670`, `254712345671`...
+    pub fn amounts(mut self, amounts: &[Decimal]) -> Self {
+        for amount in amounts {
+            let account = format!("25471234567{}", self.request.transactions.len() % 10);
+            self = self.transaction(&account, *amount);
+        }
+        self
+    }
+
+    pub fn build(self) -> PayoutRequest {
+        self.request
+    }
+}
+
+/// `PaymentLinkRequestBuilder` struct - builds the `PaymentLinksCreateDetails` of a KES 100
+/// payment link titled `Order` by default
+#[derive(Clone, Debug)]
+pub struct PaymentLinkRequestBuilder {
+    request: PaymentLinksCreateDetails,
+}
+
+impl Default for PaymentLinkRequestBuilder {
+    fn default() -> Self {
+        Self {
+            request: PaymentLinksCreateDetails {
+                title: "Order".to_string(),
+                amount: Some(100),
+                usage_limit: None,
+                is_active: None,
+                mobile_tarrif: None,
+                card_tarrif: None,
+                currency: Currency::Kes,
+                redirect_url: None,
+            },
+        }
+    }
+}
+
+impl PaymentLinkRequestBuilder {
+    /// Creates the builder of the default payment link request
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.request.title = title.to_string();
+        self
+    }
+
+    /// Sets the amount, left to the customer when `None`
+    pub fn amount(mut self, amount: Option<usize>) -> Self {
+        self.request.amount = amount;
+        self
+    }
+
+    pub fn usage_limit(mut self, usage_limit: Option<usize>) -> Self {
+        self.request.usage_limit = usage_limit;
+        self
+    }
+
+    pub fn currency(mut self, currency: Currency) -> Self {
+        self.request.currency = currency;
+        self
+    }
+
+    pub fn build(self) -> PaymentLinksCreateDetails {
+        self.request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::samples::*;
+    use super::*;
+    use crate::intasend::checkout::CheckoutResponse;
+    use crate::intasend::collection::{MpesaStkPushResponse, StkPushStatusResponse};
+    use crate::intasend::payment_links::{PaymentLink, PaymentLinksListResponse};
+    use crate::intasend::payouts::{BankCodes, Payout};
+    use crate::intasend::refunds::RefundListResponse;
+    use crate::intasend::wallets::{
+        FundCheckoutResponse, FundMpesaResponse, WalletIntraTransferResponse, WalletListResponse,
+        WalletTransactionsResponse,
+    };
+    use crate::IntasendApiError;
+    use serde::{de::DeserializeOwned, Serialize};
+    use serde_json::Value as JSON;
+
+    /// Parses `sample` as `T` and checks that serializing it gives the sample back.
+    fn round_trip<T: Serialize + DeserializeOwned>(sample: &str) {
+        let json: JSON = serde_json::from_str(sample).unwrap();
+        let model: T = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&model).unwrap(), json);
+    }
+
+    #[test]
+    fn samples_round_trip() {
+        round_trip::<MpesaStkPushResponse>(MPESA_STK_PUSH_RESPONSE);
+        round_trip::<StkPushStatusResponse>(STK_PUSH_STATUS_RESPONSE);
+        round_trip::<CheckoutResponse>(CHECKOUT_RESPONSE);
+        round_trip::<CheckoutDetailsResponse>(CHECKOUT_DETAILS_RESPONSE);
+        round_trip::<Payout>(PAYOUT);
+        round_trip::<Vec<BankCodes>>(BANK_CODES);
+        round_trip::<Refund>(REFUND);
+        round_trip::<RefundListResponse>(REFUND_LIST_RESPONSE);
+        round_trip::<Wallet>(WALLET);
+        round_trip::<WalletListResponse>(WALLET_LIST_RESPONSE);
+        round_trip::<WalletTransactionsResponse>(WALLET_TRANSACTIONS_RESPONSE);
+        round_trip::<WalletIntraTransferResponse>(WALLET_INTRA_TRANSFER_RESPONSE);
+        round_trip::<FundMpesaResponse>(FUND_MPESA_RESPONSE);
+        round_trip::<FundCheckoutResponse>(FUND_CHECKOUT_RESPONSE);
+        round_trip::<PaymentLink>(PAYMENT_LINK);
+        round_trip::<PaymentLinksListResponse>(PAYMENT_LINKS_LIST_RESPONSE);
+        round_trip::<IntasendApiError>(API_ERROR);
+    }
+
+    #[test]
+    fn sample_signatures_belong_to_their_checkout() {
+        for sample in [
+            CHECKOUT_RESPONSE,
+            CHECKOUT_DETAILS_RESPONSE,
+            FUND_CHECKOUT_RESPONSE,
+        ] {
+            let checkout: JSON = serde_json::from_str(sample).unwrap();
+            let signature = CheckoutSignature::decode(checkout["signature"].as_str().unwrap());
+            assert!(signature
+                .unwrap()
+                .belongs_to(checkout["id"].as_str().unwrap()));
+        }
+    }
+
+    #[test]
+    fn built_models_round_trip() {
+        let models = [
+            serde_json::to_string(&InvoiceBuilder::new().build()).unwrap(),
+            serde_json::to_string(&TransactionBuilder::payout(dec!(2510)).build()).unwrap(),
+            serde_json::to_string(&WalletBuilder::new().build()).unwrap(),
+            serde_json::to_string(&RefundBuilder::new().build()).unwrap(),
+            serde_json::to_string(&CheckoutDetailsBuilder::new().build()).unwrap(),
+        ];
+        round_trip::<Invoice>(&models[0]);
+        round_trip::<Transaction>(&models[1]);
+        round_trip::<Wallet>(&models[2]);
+        round_trip::<Refund>(&models[3]);
+        round_trip::<CheckoutDetailsResponse>(&models[4]);
+    }
+
+    #[test]
+    fn builders_keep_derived_fields_consistent() {
+        let invoice = InvoiceBuilder::new().value(dec!(1000)).build();
+        assert_eq!((invoice.charges, invoice.net_amount), (dec!(30), dec!(970)));
+
+        let sale = TransactionBuilder::sale(invoice).build();
+        assert_eq!(sale.value, dec!(970));
+
+        let refund = RefundBuilder::new().transaction(sale).build();
+        assert_eq!(refund.amount, dec!(970));
+
+        let partial = RefundBuilder::new().amount(dec!(5)).build();
+        assert_eq!(partial.amount, dec!(5));
+
+        let checkout = CheckoutDetailsBuilder::new().id("checkout-1").build();
+        let signature = CheckoutSignature::decode(&checkout.signature).unwrap();
+        assert!(signature.belongs_to("checkout-1"));
+    }
+}
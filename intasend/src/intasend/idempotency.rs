@@ -0,0 +1,429 @@
+//! Idempotency of the payment creating API calls.
+//!
+//! `CollectionsAPI::mpesa_stk_push`, `CheckoutsAPI::initiate`, `PayoutsAPI::initiate` (and
+//! the provider specific payout methods) and `RefundsAPI::create_with_idempotency_key` are
+//! keyed by:
+//! - the `api_ref` of the STK push and checkout requests,
+//! - the `batch_reference` of the payout requests,
+//! - the key given to `RefundsAPI::create_with_idempotency_key` for the refunds.
+//!
+//! The `api_ref` and `batch_reference` are generated (UUID v4) when absent. Set them yourself
+//! when the calls may be retried, e.g. from the ids of your orders or jobs. `RefundsAPI::create`
+//! is not deduplicated.
+//!
+//! When the client has an [`IdempotencyStore`], repeating a call with the key of a
+//! completed one returns the original response without sending a request, and repeating a
+//! call while the original one is in flight fails with
+//! `IntasendClientError::IdempotencyConflict`.
+//!
+//! A key is released when IntaSend rejects its request (4xx status) or when the request
+//! could not be built, so that it can be sent again. After the other failures (timeouts,
+//! connection resets, 5xx statuses...), or when the call is dropped before completing, the
+//! payment may still have been processed: the key becomes [`IdempotencyEntry::Unknown`] and
+//! repeating the call fails with `IntasendClientError::IdempotencyUnknown`. Check the status
+//! of the payment, then [`IdempotencyStore::release`] the key to send it again.
+//!
+//! ```rust
+//! use intasend::idempotency::MemoryIdempotencyStore;
+//! use std::time::Duration;
+//!
+//! let intasend = intasend::Intasend::new(
+//!     "publishable_key".to_string(),
+//!     "secret_key".to_string(),
+//!     true,
+//! )
+//! .with_idempotency_store(
+//!     MemoryIdempotencyStore::new()
+//!         .ttl(Duration::from_secs(6 * 60 * 60))
+//!         .capacity(50_000),
+//! );
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value as JSON;
+
+use super::{Intasend, IntasendClientError};
+
+/// The state of an idempotency key in an `IdempotencyStore`
+#[derive(Clone, Debug, PartialEq)]
+pub enum IdempotencyEntry {
+    /// The request is being sent
+    InFlight,
+    /// The request failed or was abandoned, it may still have been processed
+    Unknown,
+    /// The request completed with this (JSON) response
+    Completed(JSON),
+}
+
+/// `IdempotencyStore` trait - remembers the idempotency keys of the payment creating calls
+///
+/// Implement it on top of a shared database or cache to deduplicate calls across
+/// processes, `MemoryIdempotencyStore` only covers the clones of a single client.
+pub trait IdempotencyStore: Debug + Send + Sync + 'static {
+    /// Marks `key` as in flight, unless it is already known: the existing entry is
+    /// returned instead. This must be atomic.
+    fn begin(&self, key: &str) -> Option<IdempotencyEntry>;
+
+    /// Stores the response of the request of `key`.
+    fn complete(&self, key: &str, response: JSON);
+
+    /// Forgets `key`, allowing its request to be sent again.
+    fn release(&self, key: &str);
+
+    /// Marks `key` as unknown: its request may have been processed. The default
+    /// implementation leaves the key in flight.
+    fn mark_unknown(&self, key: &str) {
+        let _ = key;
+    }
+}
+
+/// How long `MemoryIdempotencyStore` remembers a key by default
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How many keys `MemoryIdempotencyStore` remembers by default
+const DEFAULT_CAPACITY: usize = 10_000;
+
+/// `MemoryIdempotencyStore` struct - an in-process `IdempotencyStore`
+///
+/// Keys are forgotten once their time to live (24 hours by default) elapsed, and the oldest
+/// ones first when the store holds more than its capacity (10 000 keys by default). The keys
+/// in flight or unknown are never forgotten, they must be completed or released.
+#[derive(Debug)]
+pub struct MemoryIdempotencyStore {
+    entries: Mutex<Entries>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+/// The entries of a `MemoryIdempotencyStore`, with the keys in the order they were stored.
+#[derive(Debug, Default)]
+struct Entries {
+    by_key: HashMap<String, (Instant, IdempotencyEntry)>,
+    // Keys released or stored again since are left behind, see `evict`
+    order: VecDeque<(Instant, String)>,
+}
+
+impl Default for MemoryIdempotencyStore {
+    fn default() -> Self {
+        Self {
+            entries: Mutex::default(),
+            ttl: DEFAULT_TTL,
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+}
+
+impl MemoryIdempotencyStore {
+    /// Creates an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how long the keys are remembered
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets how many keys are remembered
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// The entries, without the expired keys and the keys over capacity.
+    fn entries(&self) -> std::sync::MutexGuard<'_, Entries> {
+        let mut entries = self
+            .entries
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        entries.evict(self.ttl, self.capacity);
+        entries
+    }
+}
+
+impl Entries {
+    fn insert(&mut self, key: &str, entry: IdempotencyEntry) {
+        let stored_at = Instant::now();
+        self.by_key.insert(key.to_string(), (stored_at, entry));
+        self.order.push_back((stored_at, key.to_string()));
+    }
+
+    fn evict(&mut self, ttl: Duration, capacity: usize) {
+        // The keys in flight or unknown are skipped, and kept in order at the front
+        let mut unresolved = Vec::new();
+        while let Some((stored_at, key)) = self.order.pop_front() {
+            let Some((entry_stored_at, entry)) = self.by_key.get(&key) else {
+                continue;
+            };
+            if *entry_stored_at != stored_at {
+                continue;
+            }
+            if !matches!(entry, IdempotencyEntry::Completed(_)) {
+                unresolved.push((stored_at, key));
+                continue;
+            }
+            if stored_at.elapsed() < ttl && self.by_key.len() <= capacity {
+                self.order.push_front((stored_at, key));
+                break;
+            }
+            self.by_key.remove(&key);
+        }
+        for entry in unresolved.into_iter().rev() {
+            self.order.push_front(entry);
+        }
+    }
+}
+
+impl IdempotencyStore for MemoryIdempotencyStore {
+    fn begin(&self, key: &str) -> Option<IdempotencyEntry> {
+        let mut entries = self.entries();
+        match entries.by_key.get(key) {
+            Some((_, entry)) => Some(entry.clone()),
+            None => {
+                entries.insert(key, IdempotencyEntry::InFlight);
+                None
+            }
+        }
+    }
+
+    fn complete(&self, key: &str, response: JSON) {
+        self.entries()
+            .insert(key, IdempotencyEntry::Completed(response));
+    }
+
+    fn release(&self, key: &str) {
+        self.entries().by_key.remove(key);
+    }
+
+    fn mark_unknown(&self, key: &str) {
+        let mut entries = self.entries();
+        if let Some((_, entry)) = entries.by_key.get_mut(key) {
+            *entry = IdempotencyEntry::Unknown;
+        }
+    }
+}
+
+/// Marks the key of a request that did not complete as unknown, e.g. when its future was
+/// dropped, unless it was `resolved`.
+struct InFlightKey<'a> {
+    store: &'a dyn IdempotencyStore,
+    key: &'a str,
+    resolved: bool,
+}
+
+impl Drop for InFlightKey<'_> {
+    fn drop(&mut self) {
+        if !self.resolved {
+            self.store.mark_unknown(self.key);
+        }
+    }
+}
+
+/// Whether `error` proves that the request was not processed: rejected by IntaSend (4xx
+/// status) or never sent.
+fn was_not_processed(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<IntasendClientError>() {
+        Some(IntasendClientError::UnexpectedResponseStatus { status, .. }) => {
+            status.is_client_error()
+        }
+        Some(IntasendClientError::InvalidHeaderValue(_)) => true,
+        Some(IntasendClientError::ReqwestError(error)) => error.is_builder(),
+        _ => false,
+    }
+}
+
+/// Generates an `api_ref`/`batch_reference` for the requests without one.
+pub(crate) fn generate_reference() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+impl Intasend {
+    /// Sends the request of `key` with `send`, unless the store already knows the key.
+    pub(crate) async fn idempotent<U, F, Fut>(&self, key: String, send: F) -> anyhow::Result<U>
+    where
+        U: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<U>>,
+    {
+        let Some(store) = &self.idempotency_store else {
+            return send().await;
+        };
+
+        match store.begin(&key) {
+            Some(IdempotencyEntry::Completed(response)) => {
+                return Ok(serde_json::from_value(response)?);
+            }
+            Some(IdempotencyEntry::InFlight) => {
+                return Err(IntasendClientError::IdempotencyConflict(key).into());
+            }
+            Some(IdempotencyEntry::Unknown) => {
+                return Err(IntasendClientError::IdempotencyUnknown(key).into());
+            }
+            None => {}
+        }
+
+        let mut in_flight = InFlightKey {
+            store: store.as_ref(),
+            key: &key,
+            resolved: false,
+        };
+        let response = match send().await {
+            Ok(response) => response,
+            Err(error) => {
+                if was_not_processed(&error) {
+                    store.release(&key);
+                    in_flight.resolved = true;
+                }
+                return Err(error);
+            }
+        };
+        store.complete(&key, serde_json::to_value(&response)?);
+        in_flight.resolved = true;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntasendApiError, IntasendApiErrorDetail};
+    use futures::executor::block_on;
+    use futures::FutureExt;
+    use std::sync::Arc;
+
+    fn intasend(store: &Arc<MemoryIdempotencyStore>) -> Intasend {
+        let mut intasend = Intasend::new("pk".to_string(), "sk".to_string(), true);
+        intasend.idempotency_store = Some(store.clone());
+        intasend
+    }
+
+    fn failure(status: reqwest::StatusCode) -> anyhow::Error {
+        IntasendClientError::UnexpectedResponseStatus {
+            status,
+            error: IntasendApiError {
+                r#type: "client_error".to_string(),
+                errors: vec![IntasendApiErrorDetail {
+                    code: "invalid".to_string(),
+                    detail: "invalid".to_string(),
+                    attr: None,
+                }],
+            },
+        }
+        .into()
+    }
+
+    #[test]
+    fn repeated_keys_return_the_original_response() {
+        let store = Arc::new(MemoryIdempotencyStore::new());
+        let intasend = intasend(&store);
+
+        let first: u32 =
+            block_on(intasend.idempotent("key".to_string(), || async { Ok(1) })).unwrap();
+        let second: u32 = block_on(intasend.idempotent("key".to_string(), || async {
+            unreachable!("the request must not be sent again")
+        }))
+        .unwrap();
+
+        assert_eq!((first, second), (1, 1));
+    }
+
+    #[test]
+    fn rejected_requests_release_their_key() {
+        let store = Arc::new(MemoryIdempotencyStore::new());
+        let intasend = intasend(&store);
+
+        let rejected = block_on(
+            intasend.idempotent::<u32, _, _>("rejected".to_string(), || async {
+                Err(failure(reqwest::StatusCode::BAD_REQUEST))
+            }),
+        );
+        assert!(rejected.is_err());
+        assert_eq!(store.begin("rejected"), None);
+    }
+
+    #[test]
+    fn failed_and_dropped_requests_keep_their_key_unknown() {
+        let store = Arc::new(MemoryIdempotencyStore::new());
+        let intasend = intasend(&store);
+
+        let failed = block_on(
+            intasend.idempotent::<u32, _, _>("failed".to_string(), || async {
+                Err(failure(reqwest::StatusCode::BAD_GATEWAY))
+            }),
+        );
+        assert!(failed.is_err());
+        let retried = block_on(
+            intasend.idempotent::<u32, _, _>("failed".to_string(), || async {
+                unreachable!("the request must not be sent while its outcome is unknown")
+            }),
+        );
+        assert!(matches!(
+            retried.unwrap_err().downcast_ref::<IntasendClientError>(),
+            Some(IntasendClientError::IdempotencyUnknown(key)) if key == "failed"
+        ));
+        store.release("failed");
+        let retried =
+            block_on(intasend.idempotent::<u32, _, _>("failed".to_string(), || async { Ok(1) }));
+        assert_eq!(retried.unwrap(), 1);
+
+        let mut in_flight = Box::pin(
+            intasend.idempotent::<u32, _, _>("dropped".to_string(), futures::future::pending),
+        );
+        assert!((&mut in_flight).now_or_never().is_none());
+        let conflict = block_on(
+            intasend.idempotent::<u32, _, _>("dropped".to_string(), || async {
+                unreachable!("the request must not be sent while in flight")
+            }),
+        );
+        assert!(matches!(
+            conflict.unwrap_err().downcast_ref::<IntasendClientError>(),
+            Some(IntasendClientError::IdempotencyConflict(key)) if key == "dropped"
+        ));
+
+        drop(in_flight);
+        assert_eq!(store.begin("dropped"), Some(IdempotencyEntry::Unknown));
+    }
+
+    #[test]
+    fn memory_store_forgets_expired_keys_and_keys_over_capacity() {
+        let store = MemoryIdempotencyStore::new().capacity(2);
+        for key in ["first", "second", "third"] {
+            store.begin(key);
+            store.complete(key, JSON::from(key));
+        }
+        assert_eq!(store.begin("first"), None);
+        assert_eq!(
+            store.begin("third"),
+            Some(IdempotencyEntry::Completed(JSON::from("third")))
+        );
+
+        let store = MemoryIdempotencyStore::new().ttl(Duration::ZERO);
+        store.begin("key");
+        store.complete("key", JSON::from("key"));
+        assert_eq!(store.begin("key"), None);
+    }
+
+    #[test]
+    fn memory_store_keeps_unresolved_keys() {
+        let store = MemoryIdempotencyStore::new()
+            .capacity(2)
+            .ttl(Duration::ZERO);
+        store.begin("in flight");
+        store.begin("unknown");
+        store.mark_unknown("unknown");
+        for index in 0..10 {
+            let key = format!("key {index}");
+            store.begin(&key);
+            store.complete(&key, JSON::from(index));
+        }
+        assert_eq!(store.begin("in flight"), Some(IdempotencyEntry::InFlight));
+        assert_eq!(store.begin("unknown"), Some(IdempotencyEntry::Unknown));
+    }
+}
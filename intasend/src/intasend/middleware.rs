@@ -0,0 +1,232 @@
+//! Request/response middleware for the `Intasend` client.
+//!
+//! A [`Middleware`] hooks into every API call made by the `*API` facades:
+//! - `before_request` can inspect or modify the outgoing [`ApiRequest`] (e.g. inject
+//!   correlation headers, audit-log payout bodies) and short-circuit the call by
+//!   returning a response of its own, or abort it by returning an error.
+//! - `after_response` can inspect or modify the [`ApiResponse`] before it is parsed.
+//!
+//! The `before_request` hooks run in the order the middleware were added to the client,
+//! the `after_response` hooks run in the reverse order, and only for the middleware whose
+//! `before_request` hook ran. The credentials are added to the request after all the
+//! `before_request` hooks, so they never reach the middleware.
+//!
+//! ```rust
+//! use futures::future::{BoxFuture, FutureExt};
+//! use intasend::middleware::Middleware;
+//! use intasend::{ApiRequest, ApiResponse, IntasendClientError};
+//!
+//! /// Audit-logs every payout request with the personal data redacted
+//! #[derive(Debug)]
+//! struct PayoutAudit;
+//!
+//! impl Middleware for PayoutAudit {
+//!     fn before_request<'a>(
+//!         &'a self,
+//!         request: &'a mut ApiRequest,
+//!     ) -> BoxFuture<'a, Result<Option<ApiResponse>, IntasendClientError>> {
+//!         async move {
+//!             if request.path().starts_with("/api/v1/send-money/") {
+//!                 let mut body = request.body.clone().unwrap_or_default();
+//!                 if let Some(transactions) = body["transactions"].as_array_mut() {
+//!                     for transaction in transactions {
+//!                         transaction["name"] = "[redacted]".into();
+//!                     }
+//!                 }
+//!                 println!("[#] Payout audit: {} {}", request.path(), body);
+//!             }
+//!             Ok(None)
+//!         }
+//!         .boxed()
+//!     }
+//! }
+//!
+//! let intasend = intasend::Intasend::new(
+//!     "publishable_key".to_string(),
+//!     "secret_key".to_string(),
+//!     true,
+//! )
+//! .with_middleware(PayoutAudit);
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+
+use futures::future::{self, BoxFuture, FutureExt};
+
+use super::runtime::HttpExchange;
+use super::{ApiRequest, ApiResponse, IntasendClientError};
+
+/// `Middleware` trait - async hooks around every request sent by the `Intasend` client
+pub trait Middleware: fmt::Debug + Send + Sync + 'static {
+    /// Called before the request is sent. Returning `Ok(Some(response))` skips sending the
+    /// request (and the remaining `before_request` hooks) and uses `response` instead.
+    fn before_request<'a>(
+        &'a self,
+        request: &'a mut ApiRequest,
+    ) -> BoxFuture<'a, Result<Option<ApiResponse>, IntasendClientError>> {
+        future::ready(Ok(None)).boxed()
+    }
+
+    /// Called with the response before it is parsed.
+    fn after_response<'a>(
+        &'a self,
+        request: &'a ApiRequest,
+        response: &'a mut ApiResponse,
+    ) -> BoxFuture<'a, Result<(), IntasendClientError>> {
+        future::ready(Ok(())).boxed()
+    }
+}
+
+/// The ordered middleware of an `Intasend` client, shared by its clones.
+#[derive(Clone, Default)]
+pub(crate) struct MiddlewareChain(Vec<Arc<dyn Middleware>>);
+
+impl MiddlewareChain {
+    pub(crate) fn push(&mut self, middleware: Arc<dyn Middleware>) {
+        self.0.push(middleware);
+    }
+
+    /// Runs `request` through the chain, calling `send` unless a middleware short-circuits.
+    pub(crate) async fn run<'a, F>(
+        &self,
+        mut request: ApiRequest,
+        send: F,
+    ) -> Result<ApiResponse, IntasendClientError>
+    where
+        F: FnOnce(ApiRequest) -> HttpExchange<'a>,
+    {
+        let mut ran = 0;
+        let mut short_circuit = None;
+
+        for middleware in &self.0 {
+            ran += 1;
+            if let Some(response) = middleware.before_request(&mut request).await? {
+                short_circuit = Some(response);
+                break;
+            }
+        }
+
+        let mut response = match short_circuit {
+            Some(response) => response,
+            None => send(request.clone()).await?,
+        };
+
+        for middleware in self.0[..ran].iter().rev() {
+            middleware.after_response(&request, &mut response).await?;
+        }
+
+        Ok(response)
+    }
+}
+
+impl fmt::Debug for MiddlewareChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.0).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderMap;
+    use reqwest::{Method, StatusCode};
+    use std::sync::Mutex;
+
+    #[derive(Debug)]
+    struct Recorder {
+        name: &'static str,
+        calls: Arc<Mutex<Vec<String>>>,
+        short_circuit: bool,
+    }
+
+    impl Middleware for Recorder {
+        fn before_request<'a>(
+            &'a self,
+            request: &'a mut ApiRequest,
+        ) -> BoxFuture<'a, Result<Option<ApiResponse>, IntasendClientError>> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("before {}", self.name));
+            request
+                .headers
+                .insert("x-seen-by", self.name.parse().unwrap());
+            let response = self.short_circuit.then(|| response(StatusCode::ACCEPTED));
+            future::ready(Ok(response)).boxed()
+        }
+
+        fn after_response<'a>(
+            &'a self,
+            _request: &'a ApiRequest,
+            _response: &'a mut ApiResponse,
+        ) -> BoxFuture<'a, Result<(), IntasendClientError>> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("after {}", self.name));
+            future::ready(Ok(())).boxed()
+        }
+    }
+
+    fn request() -> ApiRequest {
+        ApiRequest {
+            method: Method::GET,
+            url: "https://sandbox.intasend.com/api/v1/wallets/".to_string(),
+            headers: HeaderMap::new(),
+            body: None,
+        }
+    }
+
+    fn response(status: StatusCode) -> ApiResponse {
+        ApiResponse {
+            status,
+            headers: HeaderMap::new(),
+            body: b"{}".to_vec(),
+        }
+    }
+
+    fn chain(calls: &Arc<Mutex<Vec<String>>>, short_circuit_second: bool) -> MiddlewareChain {
+        let mut chain = MiddlewareChain::default();
+        for (name, short_circuit) in [("a", false), ("b", short_circuit_second), ("c", false)] {
+            chain.push(Arc::new(Recorder {
+                name,
+                calls: calls.clone(),
+                short_circuit,
+            }));
+        }
+        chain
+    }
+
+    #[test]
+    fn hooks_wrap_the_request_in_order() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let response =
+            futures::executor::block_on(chain(&calls, false).run(request(), |request| {
+                assert_eq!(request.headers["x-seen-by"], "c");
+                future::ready(Ok(response(StatusCode::OK))).boxed()
+            }))
+            .unwrap();
+
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["before a", "before b", "before c", "after c", "after b", "after a"]
+        );
+    }
+
+    #[test]
+    fn short_circuit_skips_the_request() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let response = futures::executor::block_on(
+            chain(&calls, true).run(request(), |_| unreachable!("request must not be sent")),
+        )
+        .unwrap();
+
+        assert_eq!(response.status, StatusCode::ACCEPTED);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["before a", "before b", "after b", "after a"]
+        );
+    }
+}
@@ -2,25 +2,54 @@
 #![allow(unused_imports)]
 
 use anyhow::Result;
-use reqwest::{Client, Error as ReqwestErr};
+use futures::FutureExt;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, InvalidHeaderValue, AUTHORIZATION, CONTENT_TYPE,
+};
+use reqwest::{Error as ReqwestErr, Method, StatusCode};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JSON;
 use std::fmt::{self, Debug};
+use std::sync::Arc;
 use thiserror::Error as ThisErr;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub(crate) mod checkout;
+pub(crate) mod checkout_button;
+pub mod checkout_store;
 pub(crate) mod collection;
+mod de;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod fixtures;
+pub mod idempotency;
+pub mod middleware;
+pub(crate) mod payment_link_analytics;
+pub(crate) mod payment_link_template;
 pub(crate) mod payment_links;
 pub(crate) mod payouts;
+#[cfg(feature = "qrcode")]
+pub(crate) mod qr_code;
+pub mod rate_limit;
 pub(crate) mod refunds;
+pub mod runtime;
+pub mod telemetry;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod transport;
 pub(crate) mod wallets;
 
 use checkout::CheckoutsAPI;
 use collection::CollectionsAPI;
+use idempotency::IdempotencyStore;
+use middleware::{Middleware, MiddlewareChain};
 use payment_links::PaymentLinksAPI;
 use payouts::PayoutsAPI;
+use rate_limit::RateLimiter;
 use refunds::RefundsAPI;
+use runtime::{HttpExchange, Runtime};
+use transport::Transport;
 use wallets::WalletsAPI;
 
 /// **[IntaSend](https://intasend.com)** - The _Unofficial_ Rust Client SDK for the Intasend API Gateway.
@@ -29,20 +58,48 @@ use wallets::WalletsAPI;
 /// variety of payment methods e.g Visa, Mastercard, M-Pesa, and even Bitcoin.
 ///
 /// The library is fully async and it uses Reqwest library under the hood to make asynchronous calls to the REST API.
+/// It is not tied to a specific async runtime, see the `runtime` module for the supported ones.
 ///
 /// To use the library you should acquire test or production API keys here: [Sandbox](https://sandbox.intasend.com) or [Production](https://payment.intasend.com)
 /// Features include:
 ///  - `client`: Functionality for applications that need to initiate payments in browser environments
 /// - `server`: Functionality for applications that need to receive payments and manage wallets in server environments
 /// - `full`: Enables all functionality (both client and server)
+/// - `blocking`: A synchronous client, mirroring the async one, under `intasend::blocking`
+/// - `tokio`, `async-std`, `smol`: Runtime adapters for the respective async runtimes
+/// - `tracing`: A `tracing` span per API call with events for responses and errors (PII masked)
+/// - `metrics`: Request, latency and payment outcome metrics through the `metrics` crate
+/// - `tower`: Send the requests through any `tower::Service`, see `Intasend::with_service`
+/// - `testing`: An in-process mock IntaSend server for hermetic tests, see the `testing` module
+/// - `sqlite`: A SQLite `CheckoutStore`, see the `checkout_store` module
+/// - `qrcode`: SVG and PNG QR codes of the payment link and checkout URLs, see `QrCode`
+/// - `test-utils`: Builders and JSON samples of the response models, see the `fixtures` module
 ///
-/// By default, the `full` feature is exposed.
+/// Cross-cutting concerns (correlation headers, audit logs...) can be plugged into every
+/// request with `Intasend::with_middleware`.
+///
+/// By default, the `full` and `tokio` features are exposed.
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Intasend {
     publishable_key: String,
     secret_key: String,
     test_mode: bool,
+    #[serde(skip, default = "runtime::default_runtime")]
+    runtime: Arc<dyn Runtime>,
+    #[serde(skip)]
+    middleware: MiddlewareChain,
+    #[serde(skip, default = "transport::default_transport")]
+    transport: Arc<dyn Transport>,
+    #[serde(skip)]
+    rate_limiter: Option<Arc<RateLimiter>>,
+    #[serde(skip)]
+    idempotency_store: Option<Arc<dyn IdempotencyStore>>,
+    #[serde(skip)]
+    base_url: Option<String>,
+    #[cfg(feature = "metrics")]
+    #[serde(skip)]
+    stk_push_outcomes: Arc<telemetry::StkPushOutcomes>,
 }
 
 /// The Intasend Struct implements a number of methods namely: collection, checkout,
@@ -69,9 +126,157 @@ impl Intasend {
             publishable_key,
             secret_key,
             test_mode,
+            runtime: runtime::default_runtime(),
+            middleware: MiddlewareChain::default(),
+            transport: transport::default_transport(),
+            rate_limiter: None,
+            idempotency_store: None,
+            base_url: None,
+            #[cfg(feature = "metrics")]
+            stk_push_outcomes: Arc::default(),
         }
     }
 
+    /// The `with_runtime` method sets the async runtime the client runs on, overriding the one
+    /// picked from the enabled runtime features (see the `runtime` module)
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "smol")]
+    /// # {
+    /// let intasend = intasend::Intasend::new(
+    ///     "publishable_key".to_string(),
+    ///     "secret_key".to_string(),
+    ///     true,
+    /// )
+    /// .with_runtime(intasend::runtime::SmolRuntime);
+    /// # }
+    /// ```
+    pub fn with_runtime<R: Runtime>(mut self, runtime: R) -> Self {
+        self.runtime = Arc::new(runtime);
+        self
+    }
+
+    /// The `with_middleware` method appends a `Middleware` to the client's middleware chain.
+    ///
+    /// The `before_request` hooks run in the order the middleware were added, and the
+    /// `after_response` hooks in the reverse order (see the `middleware` module)
+    ///
+    /// ```rust
+    /// use futures::future::{BoxFuture, FutureExt};
+    /// use intasend::middleware::Middleware;
+    /// use intasend::{ApiRequest, ApiResponse, IntasendClientError};
+    ///
+    /// #[derive(Debug)]
+    /// struct CorrelationId(String);
+    ///
+    /// impl Middleware for CorrelationId {
+    ///     fn before_request<'a>(
+    ///         &'a self,
+    ///         request: &'a mut ApiRequest,
+    ///     ) -> BoxFuture<'a, Result<Option<ApiResponse>, IntasendClientError>> {
+    ///         async move {
+    ///             request.headers.insert("x-correlation-id", self.0.parse()?);
+    ///             Ok(None)
+    ///         }
+    ///         .boxed()
+    ///     }
+    /// }
+    ///
+    /// let intasend = intasend::Intasend::new(
+    ///     "publishable_key".to_string(),
+    ///     "secret_key".to_string(),
+    ///     true,
+    /// )
+    /// .with_middleware(CorrelationId("7b1c3f".to_string()));
+    /// ```
+    pub fn with_middleware<M: Middleware>(mut self, middleware: M) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// The `with_base_url` method sends the requests to `base_url` instead of the sandbox or
+    /// production API, e.g. to a proxy or to the `testing::MockServer`
+    ///
+    /// ```rust
+    /// let intasend = intasend::Intasend::new(
+    ///     "publishable_key".to_string(),
+    ///     "secret_key".to_string(),
+    ///     true,
+    /// )
+    /// .with_base_url("http://127.0.0.1:8080");
+    /// ```
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(base_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// The `with_rate_limiter` method limits the rate and concurrency of the requests sent by
+    /// the client, the limiter being shared by its clones and `*API` handles (see the
+    /// `rate_limit` module)
+    ///
+    /// ```rust
+    /// use intasend::rate_limit::{RateLimit, RateLimiter};
+    ///
+    /// let intasend = intasend::Intasend::new(
+    ///     "publishable_key".to_string(),
+    ///     "secret_key".to_string(),
+    ///     true,
+    /// )
+    /// .with_rate_limiter(RateLimiter::new(RateLimit::per_second(5)).max_in_flight(2));
+    /// ```
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(Arc::new(limiter));
+        self
+    }
+
+    /// The `with_idempotency_store` method deduplicates the payment creating calls
+    /// (STK pushes, checkouts, payouts and refunds) by their idempotency keys, the store
+    /// being shared by the clones of the client (see the `idempotency` module)
+    ///
+    /// ```rust
+    /// let intasend = intasend::Intasend::new(
+    ///     "publishable_key".to_string(),
+    ///     "secret_key".to_string(),
+    ///     true,
+    /// )
+    /// .with_idempotency_store(intasend::idempotency::MemoryIdempotencyStore::new());
+    /// ```
+    pub fn with_idempotency_store<S: IdempotencyStore>(mut self, store: S) -> Self {
+        self.idempotency_store = Some(Arc::new(store));
+        self
+    }
+
+    /// The `with_cassette` method records the exchanges of the client into a cassette, or
+    /// replays them from it without sending any request (see `testing::Cassette`)
+    ///
+    /// Set it after `with_service`, which replaces the transport being recorded.
+    #[cfg(feature = "testing")]
+    pub fn with_cassette(mut self, cassette: testing::Cassette) -> Self {
+        let secrets = vec![self.publishable_key.clone(), self.secret_key.clone()];
+        self.transport = cassette.wrap(self.transport, secrets);
+        self
+    }
+
+    /// The `with_service` method sends the requests through a `tower::Service`, typically the
+    /// `ReqwestTransport` wrapped in tower layers (see the `transport` module)
+    ///
+    /// The service is cloned for every request, and its errors other than
+    /// `IntasendClientError`s are returned as `IntasendClientError::Transport`.
+    #[cfg(feature = "tower")]
+    pub fn with_service<S>(mut self, service: S) -> Self
+    where
+        S: tower_service::Service<ApiRequest, Response = ApiResponse>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        S::Future: Send,
+    {
+        self.transport = Arc::new(transport::ServiceTransport(service));
+        self
+    }
+
     /// The `collection` method returns an instance of the `CollectionsAPI` struct
     ///
     /// ```rust
@@ -246,84 +451,13 @@ impl RequestClient for Intasend {
         T: Serialize,
         U: for<'de> Deserialize<'de> + Debug,
     {
-        let client = Client::new();
+        let credentials = (
+            HeaderName::from_static("x-intasend-public-api-key"),
+            HeaderValue::from_str(&self.publishable_key)?,
+        );
 
-        let base_url = if self.test_mode {
-            "https://sandbox.intasend.com"
-        } else {
-            "https://payment.intasend.com"
-        };
-
-        match request_method {
-            RequestMethods::Get => {
-                let response = client
-                    .get(format!("{}{}", base_url, service_path))
-                    .header("Content-Type", "application/json")
-                    .header("X-IntaSend-Public-API-Key", self.publishable_key.clone())
-                    .send()
-                    .await?;
-                // println!("[#] API Response: {:#?}", response);
-
-                if response.status().is_success() {
-                    let parsed_response = response.json::<U>().await?;
-                    // println!("[#] API Response (OK): {:#?}", parsed_response);
-                    Ok(parsed_response)
-                } else {
-                    let status = response.status();
-                    let error_response = response.json::<IntasendApiError>().await?;
-                    Err(IntasendClientError::UnexpectedResponseStatus {
-                        status,
-                        error: error_response,
-                    })
-                }
-            }
-            RequestMethods::Post => {
-                let response = client
-                    .post(format!("{}{}", base_url, service_path))
-                    .header("Content-Type", "application/json")
-                    .header("X-IntaSend-Public-API-Key", self.publishable_key.clone())
-                    .json(&payload)
-                    .send()
-                    .await?;
-                // println!("[#] API Response: {:#?}", response);
-
-                if response.status().is_success() {
-                    let parsed_response = response.json::<U>().await?;
-                    // println!("[#] API parsed Response (OK): {:#?}", parsed_response);
-                    Ok(parsed_response)
-                } else {
-                    let status = response.status();
-                    let error_response = response.json::<IntasendApiError>().await?;
-                    Err(IntasendClientError::UnexpectedResponseStatus {
-                        status,
-                        error: error_response,
-                    })
-                }
-            }
-            RequestMethods::Put => {
-                let response = client
-                    .put(format!("{}{}", base_url, service_path))
-                    .header("Content-Type", "application/json")
-                    .header("X-IntaSend-Public-API-Key", self.publishable_key.clone())
-                    .json(&payload)
-                    .send()
-                    .await?;
-                // println!("[#] API Response: {:#?}", response);
-
-                if response.status().is_success() {
-                    let parsed_response = response.json::<U>().await?;
-                    // println!("[#] API parsed Response (OK): {:#?}", parsed_response);
-                    Ok(parsed_response)
-                } else {
-                    let status = response.status();
-                    let error_response = response.json::<IntasendApiError>().await?;
-                    Err(IntasendClientError::UnexpectedResponseStatus {
-                        status,
-                        error: error_response,
-                    })
-                }
-            }
-        }
+        self.dispatch(payload, service_path, request_method, credentials)
+            .await
     }
 
     async fn send<T, U>(
@@ -336,91 +470,145 @@ impl RequestClient for Intasend {
         T: Serialize,
         U: for<'de> Deserialize<'de> + Debug,
     {
-        let client = Client::new();
+        let credentials = (
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", self.secret_key))?,
+        );
+
+        self.dispatch(payload, service_path, request_method, credentials)
+            .await
+    }
+}
 
-        let base_url = if self.test_mode {
+impl Intasend {
+    fn base_url(&self) -> &str {
+        if let Some(base_url) = &self.base_url {
+            base_url
+        } else if self.test_mode {
             "https://sandbox.intasend.com"
         } else {
             "https://payment.intasend.com"
+        }
+    }
+
+    /// Builds the `ApiRequest`, sends it and parses the response body, either into `U`
+    /// or into an `IntasendApiError` for unsuccessful status codes.
+    async fn dispatch<T, U>(
+        &self,
+        payload: Option<T>,
+        service_path: &str,
+        request_method: RequestMethods,
+        credentials: (HeaderName, HeaderValue),
+    ) -> Result<U, IntasendClientError>
+    where
+        T: Serialize,
+        U: for<'de> Deserialize<'de> + Debug,
+    {
+        let body = match request_method {
+            RequestMethods::Get | RequestMethods::Delete => None,
+            RequestMethods::Post | RequestMethods::Put | RequestMethods::Patch => {
+                Some(serde_json::to_value(&payload)?)
+            }
         };
 
-        match request_method {
-            RequestMethods::Get => {
-                let response = client
-                    .get(format!("{}{}", base_url, service_path))
-                    .header("Content-Type", "application/json")
-                    .header("Authorization", format!("Bearer {}", self.secret_key))
-                    // .header("X-IntaSend-Public-API-Key", self.publishable_key.clone())
-                    .send()
-                    .await?;
-                // .json()
-                // .await?;
-                // println!("[#] API Response: {:#?}", response);
-
-                // let json: Map<String, Value> = serde_json::from_str(response)?;
-                // let json = serde_json::from_value::<U>(response?.json().await?)
-                //     .expect("[!] Error parsing json!");
-
-                if response.status().is_success() {
-                    let parsed_response = response.json::<U>().await?;
-                    // println!("[#] API Response (OK): {:#?}", parsed_response);
-                    Ok(parsed_response)
-                } else {
-                    let status = response.status();
-                    let error_response = response.json::<IntasendApiError>().await?;
-                    Err(IntasendClientError::UnexpectedResponseStatus {
-                        status,
-                        error: error_response,
-                    })
-                }
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
-                // Ok(response)
-            }
-            RequestMethods::Post => {
-                let response = client
-                    .post(format!("{}{}", base_url, service_path))
-                    .header("Content-Type", "application/json")
-                    .header("Authorization", format!("Bearer {}", self.secret_key))
-                    // .header("X-IntaSend-Public-API-Key", self.publishable_key.clone())
-                    .json(&payload)
-                    .send()
-                    .await?;
-
-                if response.status().is_success() {
-                    let parsed_response = response.json::<U>().await?;
-                    // println!("[#] API Response (OK): {:#?}", parsed_response);
-                    Ok(parsed_response)
-                } else {
-                    let status = response.status();
-                    let error_response = response.json::<IntasendApiError>().await?;
-                    Err(IntasendClientError::UnexpectedResponseStatus {
-                        status,
-                        error: error_response,
-                    })
+        let request = ApiRequest {
+            method: request_method.into(),
+            url: format!("{}{}", self.base_url(), service_path),
+            headers,
+            body,
+        };
+
+        let mut attempt = 1;
+        let response = loop {
+            let response = self
+                .execute(request.clone(), credentials.clone(), attempt)
+                .await?;
+
+            let retry = self
+                .rate_limiter
+                .as_ref()
+                .and_then(|limiter| limiter.retry_delay(&response, attempt));
+            match retry {
+                Some(delay) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        attempt,
+                        delay_ms = delay.as_millis() as u64,
+                        "Throttled by IntaSend, retrying"
+                    );
+                    self.runtime.sleep(delay).await;
+                    attempt += 1;
                 }
+                None => break response,
             }
-            RequestMethods::Put => {
-                let response = client
-                    .put(format!("{}{}", base_url, service_path))
-                    .header("Content-Type", "application/json")
-                    .header("Authorization", format!("Bearer {}", self.secret_key))
-                    .json(&payload)
-                    .send()
-                    .await?;
-
-                if response.status().is_success() {
-                    let parsed_response = response.json::<U>().await?;
-                    Ok(parsed_response)
-                } else {
-                    let status = response.status();
-                    let error_response = response.json::<IntasendApiError>().await?;
-                    Err(IntasendClientError::UnexpectedResponseStatus {
-                        status,
-                        error: error_response,
-                    })
+        };
+
+        if response.status.is_success() {
+            // Deletions answer `204 No Content`, an empty body parses as `null` (e.g. into `()`)
+            let body: &[u8] = if response.body.iter().all(u8::is_ascii_whitespace) {
+                b"null"
+            } else {
+                &response.body
+            };
+            let parsed_response = serde_json::from_slice::<U>(body)?;
+            Ok(parsed_response)
+        } else {
+            let error_response = serde_json::from_slice::<IntasendApiError>(&response.body)?;
+            Err(IntasendClientError::UnexpectedResponseStatus {
+                status: response.status,
+                error: error_response,
+            })
+        }
+    }
+
+    /// Runs a single attempt of the request through the middleware chain, adding the
+    /// credentials only once the `before_request` hooks have run.
+    async fn execute(
+        &self,
+        request: ApiRequest,
+        credentials: (HeaderName, HeaderValue),
+        attempt: u32,
+    ) -> Result<ApiResponse, IntasendClientError> {
+        #[cfg(feature = "tracing")]
+        let span = telemetry::request_span(request.method.as_str(), request.path(), attempt);
+        #[cfg(feature = "tracing")]
+        telemetry::trace_request(&span, request.body.as_ref());
+        #[cfg(feature = "metrics")]
+        let method = request.method.clone();
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let started = std::time::Instant::now();
+        let path = request.path().to_string();
+        let request_path = path.as_str();
+
+        let exchange = self.middleware.run(request, |mut request| {
+            let (name, mut value) = credentials;
+            value.set_sensitive(true);
+            request.headers.insert(name, value);
+
+            let exchange = self.runtime.http(self.transport.send(request));
+            match &self.rate_limiter {
+                Some(limiter) => async move {
+                    let _in_flight = limiter.acquire(request_path, &*self.runtime).await;
+                    exchange.await
                 }
+                .boxed(),
+                None => exchange,
             }
-        }
+        });
+        #[cfg(feature = "tracing")]
+        let exchange = tracing::Instrument::instrument(exchange, span.clone());
+
+        let outcome = exchange.await;
+
+        #[cfg(feature = "tracing")]
+        telemetry::trace_outcome(&span, &outcome, started.elapsed());
+        #[cfg(feature = "metrics")]
+        telemetry::record_request(method.as_str(), &path, &outcome, started.elapsed());
+
+        outcome
     }
 }
 
@@ -461,7 +649,7 @@ pub trait RequestClient {
 // }
 
 /// `ApiErrorDatail` struct
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct IntasendApiErrorDetail {
     pub code: String,
     pub detail: String,
@@ -469,7 +657,7 @@ pub struct IntasendApiErrorDetail {
 }
 
 /// `IntasendApiError` struct
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct IntasendApiError {
     pub r#type: String,
     pub errors: Vec<IntasendApiErrorDetail>,
@@ -482,6 +670,32 @@ pub enum IntasendClientError {
     ReqwestError(#[from] ReqwestErr),
     #[error("Intasend JSON error")]
     SerdeJsonError(#[from] serde_json::Error),
+    #[error("Intasend invalid header value")]
+    InvalidHeaderValue(#[from] InvalidHeaderValue),
+    #[error("Intasend middleware error: {0}")]
+    Middleware(Box<dyn std::error::Error + Send + Sync>),
+    #[error("Intasend transport error: {0}")]
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+    #[error("Intasend request with idempotency key `{0}` is already in flight")]
+    IdempotencyConflict(String),
+    #[error("Intasend request with idempotency key `{0}` may have been processed, check its status before releasing the key")]
+    IdempotencyUnknown(String),
+    #[error("Intasend checkout signature is invalid: {0}")]
+    InvalidSignature(String),
+    #[error("Intasend store error: {0}")]
+    Store(Box<dyn std::error::Error + Send + Sync>),
+    #[error("Intasend operation timed out: {0}")]
+    Timeout(String),
+    #[error("Intasend QR code error: {0}")]
+    QrCode(Box<dyn std::error::Error + Send + Sync>),
+    #[error("Intasend payment link template error: {0}")]
+    Template(String),
+    #[error("Intasend payout import error: {0}")]
+    Import(String),
+    #[error("Intasend payout batch plan error: {0}")]
+    BatchPlan(String),
+    #[error("Intasend payout approval error: {0}")]
+    Approval(String),
     #[error("Unexpected response status: {status}\n\nError type: {0}\nDetails: {error:#?}", error.r#type)]
     UnexpectedResponseStatus {
         status: reqwest::StatusCode,
@@ -514,15 +728,21 @@ pub struct Invoice {
     pub invoice_id: String,
     pub state: String,
     pub provider: String,
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub charges: Decimal,
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub net_amount: Decimal,
     pub currency: String,
     pub value: Decimal,
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub account: String,
     pub api_ref: Option<String>,
     pub mpesa_reference: Option<String>,
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub host: String,
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub card_info: CardInfo,
+    #[serde(default, deserialize_with = "de::lenient_number")]
     pub retry_count: u32,
     pub failed_reason: Option<String>,
     pub failed_code: Option<String>,
@@ -535,6 +755,7 @@ pub struct Invoice {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Customer {
     pub customer_id: String,
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub phone_number: String,
     pub email: Option<String>,
     pub first_name: Option<String>,
@@ -547,7 +768,7 @@ pub struct Customer {
 }
 
 /// `CardInfo` struct
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct CardInfo {
     pub bin_country: Option<String>,
     pub card_type: Option<String>,
@@ -613,11 +834,61 @@ pub enum Provider {
     CoopB2b,
 }
 
+/// `ApiRequest` struct - a request on its way to the IntaSend API, as seen by middleware.
+///
+/// The credentials are only added after the `before_request` hooks have run.
+#[derive(Clone, Debug)]
+pub struct ApiRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Option<JSON>,
+}
+
+impl ApiRequest {
+    /// The path of the request `url`, e.g. `/api/v1/wallets/`
+    pub fn path(&self) -> &str {
+        let authority = self.url.find("://").map_or(0, |index| index + 3);
+        self.url[authority..]
+            .find('/')
+            .map_or("/", |index| &self.url[authority + index..])
+    }
+}
+
+/// `ApiResponse` struct - a response from the IntaSend API with its raw body
+#[derive(Clone, Debug)]
+pub struct ApiResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl ApiResponse {
+    /// Parses the response body as JSON
+    pub fn json(&self) -> Result<JSON, serde_json::Error> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
 /// `RequestMethods` enum
 pub enum RequestMethods {
     Get,
     Post,
     Put,
+    Patch,
+    Delete,
+}
+
+impl From<RequestMethods> for Method {
+    fn from(request_method: RequestMethods) -> Self {
+        match request_method {
+            RequestMethods::Get => Method::GET,
+            RequestMethods::Post => Method::POST,
+            RequestMethods::Put => Method::PUT,
+            RequestMethods::Patch => Method::PATCH,
+            RequestMethods::Delete => Method::DELETE,
+        }
+    }
 }
 
 /// Currencies supported by Intasend API Gateway
@@ -0,0 +1,418 @@
+//! Usage analytics of the payment links, computed from the collections of a wallet.
+//!
+//! IntaSend does not report which collections came through which payment link, so the
+//! collections are attributed from the wallet transactions: a sale belongs to a link when
+//! the `api_ref` of its invoice is the link id (hyphenated or not) or the link URL. The
+//! refunded sales, and those with a pending chargeback, are reported apart from the
+//! collections.
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::payment_links::{PaymentLink, PaymentLinksAPI};
+use super::wallets::WalletTransactionsResponse;
+use super::{RequestClient, RequestMethods, Transaction, TransactionStatus, TransactionType};
+
+/// Columns of `PaymentLinkAnalytics::to_csv`
+const CSV_HEADER: &str = "link_id,title,currency,is_active,uses,usage_limit,remaining_uses,total_collected,total_fees,net_collected,refunds,total_refunded,last_payment_at";
+
+/// `PaymentLinkAnalytics` struct - how much a payment link was used and collected
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PaymentLinkAnalytics {
+    pub link_id: Uuid,
+    pub title: String,
+    pub currency: String,
+    pub is_active: bool,
+    /// Number of payments made through the link
+    pub uses: u32,
+    /// Maximum number of payments of the link, `0` when unlimited
+    pub usage_limit: i32,
+    /// Payments left before the usage limit, `None` when unlimited
+    pub remaining_uses: Option<u32>,
+    /// Amount paid by the customers, charges included
+    pub total_collected: Decimal,
+    /// Charges deducted by IntaSend
+    pub total_fees: Decimal,
+    /// Amount credited to the wallet
+    pub net_collected: Decimal,
+    /// Number of payments refunded, or with a pending chargeback, not counted in `uses`
+    pub refunds: u32,
+    /// Amount paid by the customers of the `refunds`
+    pub total_refunded: Decimal,
+    /// Creation time of the last payment, as reported by IntaSend
+    pub last_payment_at: Option<String>,
+}
+
+impl PaymentLinkAnalytics {
+    /// Computes the analytics of `link` from `transactions`, ignoring the transactions of
+    /// other links
+    pub fn from_transactions<'a, I>(link: &PaymentLink, transactions: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Transaction>,
+    {
+        let mut analytics = Self {
+            link_id: link.id,
+            title: link.title.clone(),
+            currency: link.currency.clone(),
+            is_active: link.is_active,
+            uses: 0,
+            usage_limit: link.usage_limit,
+            remaining_uses: None,
+            total_collected: Decimal::ZERO,
+            total_fees: Decimal::ZERO,
+            net_collected: Decimal::ZERO,
+            refunds: 0,
+            total_refunded: Decimal::ZERO,
+            last_payment_at: None,
+        };
+
+        let mut last_payment: Option<DateTime<FixedOffset>> = None;
+        for transaction in transactions {
+            let Some(invoice) = transaction
+                .invoice
+                .as_ref()
+                .filter(|_| matches!(transaction.trans_type, TransactionType::Sale))
+            else {
+                continue;
+            };
+            if !invoice
+                .api_ref
+                .as_deref()
+                .is_some_and(|api_ref| is_link_reference(link, api_ref))
+            {
+                continue;
+            }
+
+            match transaction.status {
+                TransactionStatus::Cancelled => continue,
+                TransactionStatus::Refunded | TransactionStatus::ChargebackPending => {
+                    analytics.refunds += 1;
+                    analytics.total_refunded += invoice.value;
+                    continue;
+                }
+                _ => {}
+            }
+
+            analytics.uses += 1;
+            analytics.total_collected += invoice.value;
+            analytics.total_fees += invoice.charges;
+            analytics.net_collected += invoice.net_amount;
+            // The creation times without a valid RFC 3339 timestamp are ignored
+            if let Ok(created_at) = DateTime::parse_from_rfc3339(&transaction.created_at) {
+                if last_payment.is_none_or(|last| last < created_at) {
+                    last_payment = Some(created_at);
+                    analytics.last_payment_at = Some(transaction.created_at.clone());
+                }
+            }
+        }
+
+        if link.usage_limit > 0 {
+            analytics.remaining_uses =
+                Some((link.usage_limit as u32).saturating_sub(analytics.uses));
+        }
+
+        analytics
+    }
+
+    /// Whether the link reached its usage limit
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining_uses == Some(0)
+    }
+
+    /// Renders `analytics` as CSV, with a header line and the amounts to two decimals
+    pub fn to_csv(analytics: &[Self]) -> String {
+        let mut csv = format!("{}\n", CSV_HEADER);
+        for link in analytics {
+            let fields = [
+                link.link_id.to_string(),
+                link.title.clone(),
+                link.currency.clone(),
+                link.is_active.to_string(),
+                link.uses.to_string(),
+                link.usage_limit.to_string(),
+                link.remaining_uses
+                    .map(|remaining| remaining.to_string())
+                    .unwrap_or_default(),
+                format!("{:.2}", link.total_collected),
+                format!("{:.2}", link.total_fees),
+                format!("{:.2}", link.net_collected),
+                link.refunds.to_string(),
+                format!("{:.2}", link.total_refunded),
+                link.last_payment_at.clone().unwrap_or_default(),
+            ];
+            let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+impl PaymentLinksAPI {
+    /// The `analytics` (PaymentLinksAPI) computes the usage of a payment link from the
+    /// transactions of the wallet it collects into, see `PaymentLinkAnalytics`
+    ///
+    /// ```rust
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let intasend = intasend::Intasend::new(
+    ///     std::env::var("INTASEND_PUBLIC_KEY")?,
+    ///     std::env::var("INTASEND_SECRET_KEY")?,
+    ///     true,
+    /// );
+    /// let payment_links = intasend.payment_links();
+    ///
+    /// let link = payment_links.details("e4f6126d-b374-4edb-bf17-f9240d24d66e".to_string()).await?;
+    /// let analytics = payment_links.analytics(&link, "NRWZQ9Q".to_string()).await?;
+    /// println!("{} uses, {} {} collected", analytics.uses, analytics.total_collected, analytics.currency);
+    ///
+    /// // Every link at once, as CSV
+    /// let links = payment_links.list().await?.results;
+    /// let analytics = payment_links.analytics_many(&links, "NRWZQ9Q".to_string()).await?;
+    /// std::fs::write("payment_links.csv", intasend::PaymentLinkAnalytics::to_csv(&analytics))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn analytics(
+        &self,
+        link: &PaymentLink,
+        wallet_id: String,
+    ) -> Result<PaymentLinkAnalytics> {
+        let transactions = self.wallet_transactions(wallet_id).await?;
+        Ok(PaymentLinkAnalytics::from_transactions(link, &transactions))
+    }
+
+    /// The `analytics_many` (PaymentLinksAPI) computes the usage of several payment links
+    /// collecting into the same wallet, fetching its transactions once
+    pub async fn analytics_many(
+        &self,
+        links: &[PaymentLink],
+        wallet_id: String,
+    ) -> Result<Vec<PaymentLinkAnalytics>> {
+        let transactions = self.wallet_transactions(wallet_id).await?;
+        Ok(links
+            .iter()
+            .map(|link| PaymentLinkAnalytics::from_transactions(link, &transactions))
+            .collect())
+    }
+
+    /// Fetches every page of the transactions of `wallet_id`.
+    async fn wallet_transactions(&self, wallet_id: String) -> Result<Vec<Transaction>> {
+        let mut service_path = format!("/api/v1/wallets/{}/transactions/", wallet_id);
+        let mut transactions = Vec::new();
+        loop {
+            let page = self
+                .intasend
+                .send::<(), WalletTransactionsResponse>(None, &service_path, RequestMethods::Get)
+                .await?;
+            transactions.extend(page.results);
+
+            // The cursors are absolute URLs, the client adds its own base URL
+            match page
+                .next
+                .as_deref()
+                .and_then(|next| next.find("/api/").map(|start| &next[start..]))
+            {
+                Some(next) if next != service_path => service_path = next.to_string(),
+                _ => break,
+            }
+        }
+        Ok(transactions)
+    }
+}
+
+fn is_link_reference(link: &PaymentLink, api_ref: &str) -> bool {
+    let api_ref = api_ref.trim();
+    Uuid::parse_str(api_ref).is_ok_and(|id| id == link.id)
+        || api_ref.trim_end_matches('/') == link.url.trim_end_matches('/')
+}
+
+/// Quotes `field` when it contains a CSV delimiter.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intasend::fixtures::{InvoiceBuilder, TransactionBuilder};
+    use rust_decimal_macros::dec;
+
+    fn link(usage_limit: i32) -> PaymentLink {
+        serde_json::from_str::<PaymentLink>(crate::intasend::fixtures::samples::PAYMENT_LINK)
+            .map(|link| PaymentLink {
+                usage_limit,
+                title: "Order, \"large\"".to_string(),
+                ..link
+            })
+            .unwrap()
+    }
+
+    fn sale(api_ref: &str, value: Decimal, created_at: &str) -> Transaction {
+        let invoice = InvoiceBuilder::new()
+            .value(value)
+            .api_ref(Some(api_ref))
+            .build();
+        Transaction {
+            created_at: created_at.to_string(),
+            ..TransactionBuilder::sale(invoice).build()
+        }
+    }
+
+    #[test]
+    fn last_payment_is_compared_across_offsets() {
+        let link = link(0);
+        let transactions = [
+            sale(&link.url, dec!(100), "2024-01-02T12:00:00+03:00"),
+            sale(&link.url, dec!(100), "2024-01-02T10:00:00Z"),
+            sale(&link.url, dec!(100), "2024-01-02T09:30:00.000000Z"),
+            sale(&link.url, dec!(100), "not a timestamp"),
+        ];
+
+        let analytics = PaymentLinkAnalytics::from_transactions(&link, &transactions);
+        assert_eq!(analytics.uses, 4);
+        assert_eq!(
+            analytics.last_payment_at.as_deref(),
+            Some("2024-01-02T10:00:00Z")
+        );
+    }
+
+    #[test]
+    fn collections_are_attributed_to_their_link() {
+        let link = link(3);
+        let transactions = [
+            sale(&link.id.to_string(), dec!(1000), "2024-01-02T10:00:00Z"),
+            sale(
+                &link.id.simple().to_string(),
+                dec!(500),
+                "2024-01-03T10:00:00Z",
+            ),
+            sale(&link.url, dec!(200), "2024-01-01T10:00:00Z"),
+            sale("order-1", dec!(9999), "2024-01-04T10:00:00Z"),
+            TransactionBuilder::payout(dec!(100)).build(),
+            Transaction {
+                status: TransactionStatus::Refunded,
+                ..sale(&link.url, dec!(300), "2024-01-05T10:00:00Z")
+            },
+            Transaction {
+                status: TransactionStatus::ChargebackPending,
+                ..sale(&link.url, dec!(400), "2024-01-06T10:00:00Z")
+            },
+            Transaction {
+                status: TransactionStatus::Cancelled,
+                ..sale(&link.url, dec!(500), "2024-01-07T10:00:00Z")
+            },
+        ];
+
+        let analytics = PaymentLinkAnalytics::from_transactions(&link, &transactions);
+        assert_eq!(analytics.uses, 3);
+        assert_eq!(analytics.remaining_uses, Some(0));
+        assert!(analytics.is_exhausted());
+        assert_eq!(analytics.total_collected, dec!(1700));
+        assert_eq!(analytics.total_fees, dec!(51));
+        assert_eq!(analytics.net_collected, dec!(1649));
+        assert_eq!(analytics.refunds, 2);
+        assert_eq!(analytics.total_refunded, dec!(700));
+        assert_eq!(
+            analytics.last_payment_at.as_deref(),
+            Some("2024-01-03T10:00:00Z")
+        );
+
+        let unlimited = PaymentLinkAnalytics::from_transactions(&self::link(0), &[]);
+        assert_eq!(unlimited.remaining_uses, None);
+        assert!(!unlimited.is_exhausted());
+    }
+
+    #[test]
+    fn analytics_are_exported_as_csv() {
+        let link = link(0);
+        let transactions = [sale(
+            &link.id.to_string(),
+            dec!(1000),
+            "2024-01-02T10:00:00Z",
+        )];
+        let csv = PaymentLinkAnalytics::to_csv(&[PaymentLinkAnalytics::from_transactions(
+            &link,
+            &transactions,
+        )]);
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            format!(
+                "{},\"Order, \"\"large\"\"\",{},{},1,0,,1000.00,30.00,970.00,0,0.00,2024-01-02T10:00:00Z",
+                link.id, link.currency, link.is_active
+            )
+        );
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod mock_server_tests {
+    use crate::intasend::fixtures::PaymentLinkRequestBuilder;
+    use crate::testing::MockServer;
+    use crate::{MpesaStkPushRequest, StkPushStatusRequest};
+    use rust_decimal_macros::dec;
+
+    #[tokio::test]
+    async fn payment_link_collections_are_reported() {
+        let server = MockServer::start();
+        let intasend = server.client();
+
+        let link = intasend
+            .payment_links()
+            .create(
+                PaymentLinkRequestBuilder::new()
+                    .amount(Some(1000))
+                    .usage_limit(Some(2))
+                    .build(),
+            )
+            .await
+            .unwrap();
+
+        for api_ref in [Some(link.id.to_string()), None] {
+            let response = intasend
+                .collection()
+                .mpesa_stk_push(MpesaStkPushRequest {
+                    amount: dec!(1000),
+                    phone_number: "254712345678".to_string(),
+                    api_ref,
+                    wallet_id: None,
+                })
+                .await
+                .unwrap();
+            for _ in 0..2 {
+                intasend
+                    .collection()
+                    .status(StkPushStatusRequest {
+                        invoice_id: response.invoice.clone().unwrap().invoice_id,
+                        checkout_id: None,
+                        signature: None,
+                    })
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let wallet_id = intasend.wallets().list().await.unwrap().results[0]
+            .wallet_id
+            .clone();
+        let analytics = intasend
+            .payment_links()
+            .analytics(&link, wallet_id)
+            .await
+            .unwrap();
+        assert_eq!(analytics.uses, 1);
+        assert_eq!(analytics.remaining_uses, Some(1));
+        assert_eq!(analytics.total_collected, dec!(1000));
+        assert_eq!(analytics.total_fees, dec!(30));
+        assert!(analytics.last_payment_at.is_some());
+    }
+}
@@ -0,0 +1,325 @@
+//! Templates of near-identical payment links, and their bulk creation.
+//!
+//! A [`PaymentLinkTemplate`] holds the shared settings of the links, its title, amount and
+//! redirect URL may contain `{name}` placeholders replaced by the values of each link. The
+//! values are percent-encoded in the redirect URL.
+//! `PaymentLinksAPI::create_many` then creates the rendered links concurrently.
+
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+
+use super::payment_links::{PaymentLink, PaymentLinksAPI, PaymentLinksCreateDetails};
+use super::{Currency, IntasendClientError, Tarrif};
+
+/// `PaymentLinkTemplate` struct - the settings shared by payment links, with `{name}`
+/// placeholders in their title, amount and redirect URL
+///
+/// ```rust
+/// let template = intasend::PaymentLinkTemplate::new("Summit 2025 - {tier}", intasend::Currency::Kes)
+///     .amount("{price}")
+///     .usage_limit(100)
+///     .redirect_url("https://example.com/tickets/{tier}/thanks");
+///
+/// let vip = template.render(&[("tier", "vip"), ("price", "15000")])?;
+/// assert_eq!(vip.title, "Summit 2025 - vip");
+/// assert_eq!(vip.amount, Some(15000));
+/// assert_eq!(vip.redirect_url.as_deref(), Some("https://example.com/tickets/vip/thanks"));
+/// # Ok::<(), intasend::IntasendClientError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct PaymentLinkTemplate {
+    title: String,
+    amount: Option<String>,
+    redirect_url: Option<String>,
+    usage_limit: Option<usize>,
+    is_active: Option<bool>,
+    mobile_tarrif: Option<Tarrif>,
+    card_tarrif: Option<Tarrif>,
+    currency: Currency,
+}
+
+impl PaymentLinkTemplate {
+    /// Creates the template of links titled `title`, in `currency`
+    pub fn new(title: &str, currency: Currency) -> Self {
+        Self {
+            title: title.to_string(),
+            amount: None,
+            redirect_url: None,
+            usage_limit: None,
+            is_active: None,
+            mobile_tarrif: None,
+            card_tarrif: None,
+            currency,
+        }
+    }
+
+    /// Sets the amount of the links, a whole number once rendered; left to the customer if unset
+    pub fn amount(mut self, amount: &str) -> Self {
+        self.amount = Some(amount.to_string());
+        self
+    }
+
+    /// Sets the URL the customers are redirected to after paying
+    pub fn redirect_url(mut self, redirect_url: &str) -> Self {
+        self.redirect_url = Some(redirect_url.to_string());
+        self
+    }
+
+    /// Sets the number of payments accepted by each link
+    pub fn usage_limit(mut self, usage_limit: usize) -> Self {
+        self.usage_limit = Some(usage_limit);
+        self
+    }
+
+    /// Sets whether the links are created active
+    pub fn is_active(mut self, is_active: bool) -> Self {
+        self.is_active = Some(is_active);
+        self
+    }
+
+    /// Sets who pays the fees of both the M-Pesa and card payments
+    pub fn tarrif(mut self, tarrif: Tarrif) -> Self {
+        self.mobile_tarrif = Some(tarrif.clone());
+        self.card_tarrif = Some(tarrif);
+        self
+    }
+
+    /// Sets who pays the fees of the M-Pesa payments
+    pub fn mobile_tarrif(mut self, tarrif: Tarrif) -> Self {
+        self.mobile_tarrif = Some(tarrif);
+        self
+    }
+
+    /// Sets who pays the fees of the card payments
+    pub fn card_tarrif(mut self, tarrif: Tarrif) -> Self {
+        self.card_tarrif = Some(tarrif);
+        self
+    }
+
+    /// Renders the link of `values`, failing on a placeholder without value or an amount
+    /// that is not a whole number
+    pub fn render(
+        &self,
+        values: &[(&str, &str)],
+    ) -> Result<PaymentLinksCreateDetails, IntasendClientError> {
+        let amount = match &self.amount {
+            Some(amount) => {
+                let amount = substitute(amount, values)?;
+                Some(amount.trim().parse::<usize>().map_err(|_| {
+                    IntasendClientError::Template(format!(
+                        "amount `{}` is not a whole number",
+                        amount
+                    ))
+                })?)
+            }
+            None => None,
+        };
+
+        Ok(PaymentLinksCreateDetails {
+            title: substitute(&self.title, values)?,
+            amount,
+            usage_limit: self.usage_limit,
+            is_active: self.is_active,
+            mobile_tarrif: self.mobile_tarrif.clone(),
+            card_tarrif: self.card_tarrif.clone(),
+            currency: self.currency.clone(),
+            redirect_url: self
+                .redirect_url
+                .as_deref()
+                .map(|redirect_url| substitute_with(redirect_url, values, percent_encode))
+                .transpose()?,
+        })
+    }
+}
+
+impl PaymentLinksAPI {
+    /// The `create_many` (PaymentLinksAPI) creates payment links, at most `concurrency` at
+    /// a time, returning the result of each link in the order of `payloads`
+    ///
+    /// A failed link does not stop the creation of the others.
+    ///
+    /// ```rust
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let intasend = intasend::Intasend::new(
+    ///     std::env::var("INTASEND_PUBLIC_KEY")?,
+    ///     std::env::var("INTASEND_SECRET_KEY")?,
+    ///     true,
+    /// );
+    ///
+    /// let template = intasend::PaymentLinkTemplate::new("Summit 2025 - {tier}", intasend::Currency::Kes)
+    ///     .amount("{price}");
+    /// let payloads = [("regular", "5000"), ("vip", "15000")]
+    ///     .iter()
+    ///     .map(|(tier, price)| template.render(&[("tier", tier), ("price", price)]))
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    ///
+    /// for result in intasend.payment_links().create_many(payloads, 4).await {
+    ///     match result {
+    ///         Ok(link) => println!("[#] {}: {}", link.title, link.url),
+    ///         Err(error) => eprintln!("[!] {}", error),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_many<I>(&self, payloads: I, concurrency: usize) -> Vec<Result<PaymentLink>>
+    where
+        I: IntoIterator<Item = PaymentLinksCreateDetails>,
+    {
+        stream::iter(payloads)
+            .map(|payload| self.create(payload))
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+}
+
+/// Replaces the `{name}` placeholders of `template` by their value.
+fn substitute(template: &str, values: &[(&str, &str)]) -> Result<String, IntasendClientError> {
+    substitute_with(template, values, str::to_string)
+}
+
+/// Replaces the `{name}` placeholders of `template` by their value, escaped by `escape`.
+fn substitute_with(
+    template: &str,
+    values: &[(&str, &str)],
+    escape: fn(&str) -> String,
+) -> Result<String, IntasendClientError> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let name = placeholder[1..]
+            .find('}')
+            .map(|end| &placeholder[1..=end])
+            .filter(|name| {
+                !name.is_empty()
+                    && name
+                        .chars()
+                        .all(|character| character.is_ascii_alphanumeric() || character == '_')
+            });
+
+        match name {
+            Some(name) => {
+                let value = values
+                    .iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| *value)
+                    .ok_or_else(|| {
+                        IntasendClientError::Template(format!("no value for `{{{}}}`", name))
+                    })?;
+                rendered.push_str(&escape(value));
+                rest = &placeholder[name.len() + 2..];
+            }
+            // Not a placeholder, e.g. a literal brace
+            None => {
+                rendered.push('{');
+                rest = &placeholder[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Percent-encodes the bytes of `value` other than the unreserved characters of RFC 3986.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_are_substituted() {
+        let values = [("tier", "vip"), ("price", "15000")];
+        assert_eq!(
+            substitute(
+                "{tier} ticket ({price} KES) {not a placeholder} {}",
+                &values
+            )
+            .unwrap(),
+            "vip ticket (15000 KES) {not a placeholder} {}"
+        );
+        assert!(matches!(
+            substitute("{tier} - {day}", &values),
+            Err(IntasendClientError::Template(message)) if message == "no value for `{day}`"
+        ));
+    }
+
+    #[test]
+    fn redirect_url_values_are_percent_encoded() {
+        let template = PaymentLinkTemplate::new("Summit - {tier}", Currency::Kes)
+            .redirect_url("https://example.com/tickets/{tier}/thanks?{query}");
+
+        let link = template
+            .render(&[("tier", "vip & friends/2"), ("query", "é")])
+            .unwrap();
+        assert_eq!(link.title, "Summit - vip & friends/2");
+        assert_eq!(
+            link.redirect_url.as_deref(),
+            Some("https://example.com/tickets/vip%20%26%20friends%2F2/thanks?%C3%A9")
+        );
+    }
+
+    #[test]
+    fn rendered_amounts_must_be_whole_numbers() {
+        let template = PaymentLinkTemplate::new("Ticket", Currency::Kes)
+            .amount("{price}")
+            .tarrif(Tarrif::CustomerPays);
+
+        let link = template.render(&[("price", " 2500 ")]).unwrap();
+        assert_eq!(link.amount, Some(2500));
+        assert!(matches!(link.card_tarrif, Some(Tarrif::CustomerPays)));
+        assert!(template.render(&[("price", "25.50")]).is_err());
+        assert_eq!(
+            PaymentLinkTemplate::new("Donation", Currency::Usd)
+                .render(&[])
+                .unwrap()
+                .amount,
+            None
+        );
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod mock_server_tests {
+    use super::*;
+    use crate::testing::MockServer;
+    use rust_decimal_macros::dec;
+
+    #[tokio::test]
+    async fn payment_links_are_created_in_bulk() {
+        let server = MockServer::start();
+        let payment_links = server.client().payment_links();
+
+        let template = PaymentLinkTemplate::new("{tier}", Currency::Kes)
+            .amount("{price}")
+            .usage_limit(50);
+        let payloads: Vec<_> = [("regular", "5000"), ("", "100"), ("vip", "15000")]
+            .iter()
+            .map(|(tier, price)| {
+                template
+                    .render(&[("tier", tier), ("price", price)])
+                    .unwrap()
+            })
+            .collect();
+
+        let results = payment_links.create_many(payloads, 2).await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().title, "regular");
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap().amount, dec!(15000));
+        assert_eq!(payment_links.list().await.unwrap().results.len(), 2);
+    }
+}
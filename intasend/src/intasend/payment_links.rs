@@ -10,13 +10,14 @@ use uuid::Uuid;
 
 use crate::Intasend;
 
-use super::{Currency, Customer, Invoice, RequestClient, RequestMethods, Tarrif};
+use super::{de, Currency, Customer, Invoice, RequestClient, RequestMethods, Tarrif};
 
 /// `PaymentLinksAPI` struct implements methods for facilitating:
 /// 1. Listing payment links
 /// 2. Querying details of single payment links
 /// 3. Creating new payment links
 /// 4. Update details of single payment links
+/// 5. Activating, deactivating and deleting payment links
 ///
 /// ```rust
 /// // Load .env file
@@ -176,6 +177,8 @@ impl PaymentLinksAPI {
 
     /// The `update` (PaymentLinksAPI) will help you to update details of a payment link(s).
     ///
+    /// Only the fields set in the `PaymentLinksUpdateDetails` are changed.
+    ///
     /// ```rust
     /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
     /// dotenvy::dotenv().ok();
@@ -194,14 +197,10 @@ impl PaymentLinksAPI {
     /// let payment_links: intasend::PaymentLinksAPI = intasend.payment_links();
     ///
     /// let payload = intasend::PaymentLinksUpdateDetails {
-    ///     title: "Payment link Title Updated".to_string(),
+    ///     title: Some("Payment link Title Updated".to_string()),
     ///     amount: Some(300),
     ///     usage_limit: Some(6),
-    ///     is_active: Some(false),
-    ///     mobile_tarrif: Some(intasend::Tarrif::BusinessPays),
-    ///     card_tarrif: Some(intasend::Tarrif::BusinessPays),
-    ///     currency: intasend:: Currency::Kes,
-    ///     redirect_url: None,
+    ///     ..Default::default()
     /// };
     ///
     /// let uid = uuid::Uuid::parse_str("e4f6126d-b374-4edb-bf17-f9240d24d66e").unwrap();
@@ -217,15 +216,118 @@ impl PaymentLinksAPI {
         payload: PaymentLinksUpdateDetails,
     ) -> Result<PaymentLink> {
         let service_path: &str = &format!("/api/v1/paymentlinks/{}", payment_link_id);
-        let request_method: RequestMethods = RequestMethods::Put;
+        let request_method: RequestMethods = RequestMethods::Patch;
 
         let payment_links_details = &self
             .intasend
-            .send::<PaymentLinksUpdateDetails, PaymentLink>(None, service_path, request_method)
+            .send::<PaymentLinksUpdateDetails, PaymentLink>(
+                Some(payload),
+                service_path,
+                request_method,
+            )
             .await?;
 
         Ok(payment_links_details.clone())
     }
+
+    /// The `activate` (PaymentLinksAPI) enables a payment link, customers can pay through it again.
+    ///
+    /// ```rust
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// dotenvy::dotenv().ok();
+    ///
+    /// let intasend_public_key = std::env::var("INTASEND_PUBLIC_KEY").expect("INTASEND_PUBLIC_KEY must be set");
+    /// let intasend_secret_key = std::env::var("INTASEND_SECRET_KEY").expect("INTASEND_SECRET_KEY must be set");
+    ///
+    /// // Intasend Client
+    /// let intasend = intasend::Intasend::new(
+    ///    intasend_public_key,
+    ///    intasend_secret_key,
+    ///     true,
+    /// );
+    ///
+    /// // PaymentLinksAPI
+    /// let payment_links: intasend::PaymentLinksAPI = intasend.payment_links();
+    /// let payment_link: intasend::PaymentLink = payment_links.activate("e4f6126d-b374-4edb-bf17-f9240d24d66e".to_string()).await?;
+    /// assert!(payment_link.is_active);
+    ///
+    /// Ok(())
+    /// # }
+    /// ```
+    pub async fn activate(&self, payment_link_id: String) -> Result<PaymentLink> {
+        self.set_active(payment_link_id, true).await
+    }
+
+    /// The `deactivate` (PaymentLinksAPI) disables a payment link, without deleting it.
+    ///
+    /// ```rust
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// dotenvy::dotenv().ok();
+    ///
+    /// let intasend_public_key = std::env::var("INTASEND_PUBLIC_KEY").expect("INTASEND_PUBLIC_KEY must be set");
+    /// let intasend_secret_key = std::env::var("INTASEND_SECRET_KEY").expect("INTASEND_SECRET_KEY must be set");
+    ///
+    /// // Intasend Client
+    /// let intasend = intasend::Intasend::new(
+    ///    intasend_public_key,
+    ///    intasend_secret_key,
+    ///     true,
+    /// );
+    ///
+    /// // PaymentLinksAPI
+    /// let payment_links: intasend::PaymentLinksAPI = intasend.payment_links();
+    /// let payment_link: intasend::PaymentLink = payment_links.deactivate("e4f6126d-b374-4edb-bf17-f9240d24d66e".to_string()).await?;
+    /// assert!(!payment_link.is_active);
+    ///
+    /// Ok(())
+    /// # }
+    /// ```
+    pub async fn deactivate(&self, payment_link_id: String) -> Result<PaymentLink> {
+        self.set_active(payment_link_id, false).await
+    }
+
+    /// The `delete` (PaymentLinksAPI) deletes a payment link.
+    ///
+    /// ```rust
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// dotenvy::dotenv().ok();
+    ///
+    /// let intasend_public_key = std::env::var("INTASEND_PUBLIC_KEY").expect("INTASEND_PUBLIC_KEY must be set");
+    /// let intasend_secret_key = std::env::var("INTASEND_SECRET_KEY").expect("INTASEND_SECRET_KEY must be set");
+    ///
+    /// // Intasend Client
+    /// let intasend = intasend::Intasend::new(
+    ///    intasend_public_key,
+    ///    intasend_secret_key,
+    ///     true,
+    /// );
+    ///
+    /// // PaymentLinksAPI
+    /// let payment_links: intasend::PaymentLinksAPI = intasend.payment_links();
+    /// payment_links.delete("e4f6126d-b374-4edb-bf17-f9240d24d66e".to_string()).await?;
+    ///
+    /// Ok(())
+    /// # }
+    /// ```
+    pub async fn delete(&self, payment_link_id: String) -> Result<()> {
+        let service_path: &str = &format!("/api/v1/paymentlinks/{}", payment_link_id);
+        let request_method: RequestMethods = RequestMethods::Delete;
+
+        self.intasend
+            .send::<PaymentLinksDetailsRequest, ()>(None, service_path, request_method)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_active(&self, payment_link_id: String, is_active: bool) -> Result<PaymentLink> {
+        let payload = PaymentLinksUpdateDetails {
+            is_active: Some(is_active),
+            ..Default::default()
+        };
+
+        self.update(payment_link_id, payload).await
+    }
 }
 
 /// `PaymentLink` struct
@@ -233,9 +335,12 @@ impl PaymentLinksAPI {
 pub struct PaymentLink {
     pub id: Uuid,
     pub title: String,
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub is_active: bool,
     pub redirect_url: Option<String>,
-    pub amount: i32,
+    #[serde(default, deserialize_with = "de::null_as_default")]
+    pub amount: Decimal,
+    #[serde(default, deserialize_with = "de::lenient_number")]
     pub usage_limit: i32,
     pub qrcode_file: Option<String>,
     pub url: String,
@@ -253,9 +358,12 @@ pub struct PaymentLinksListRequest {}
 /// `PaymentLinksListResponse` struct
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PaymentLinksListResponse {
+    #[serde(default, deserialize_with = "de::lenient_number")]
     pub count: usize,
-    pub next: Option<usize>,
-    pub previous: Option<usize>,
+    #[serde(default, deserialize_with = "de::lenient_string")]
+    pub next: Option<String>,
+    #[serde(default, deserialize_with = "de::lenient_string")]
+    pub previous: Option<String>,
     pub results: Vec<PaymentLink>,
 }
 
@@ -276,14 +384,81 @@ pub struct PaymentLinksCreateDetails {
     pub redirect_url: Option<String>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// `PaymentLinksUpdateDetails` struct - a partial update of a payment link, the fields left
+/// to `None` are not changed
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct PaymentLinksUpdateDetails {
-    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub amount: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub usage_limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub is_active: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub mobile_tarrif: Option<Tarrif>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub card_tarrif: Option<Tarrif>,
-    pub currency: Currency,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<Currency>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub redirect_url: Option<String>,
 }
+
+#[cfg(all(test, feature = "testing"))]
+mod mock_server_tests {
+    use super::*;
+    use crate::intasend::fixtures::PaymentLinkRequestBuilder;
+    use crate::testing::MockServer;
+    use rust_decimal_macros::dec;
+
+    #[tokio::test]
+    async fn payment_links_are_partially_updated_and_deleted() {
+        let server = MockServer::start();
+        let payment_links = server.client().payment_links();
+
+        let link = payment_links
+            .create(
+                PaymentLinkRequestBuilder::new()
+                    .usage_limit(Some(1))
+                    .build(),
+            )
+            .await
+            .unwrap();
+        let link_id = link.id.to_string();
+
+        let updated = payment_links
+            .update(
+                link_id.clone(),
+                PaymentLinksUpdateDetails {
+                    usage_limit: Some(5),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated.title, "Order");
+        assert_eq!(updated.amount, dec!(100));
+        assert_eq!(updated.usage_limit, 5);
+
+        assert!(
+            !payment_links
+                .deactivate(link_id.clone())
+                .await
+                .unwrap()
+                .is_active
+        );
+        assert!(
+            payment_links
+                .activate(link_id.clone())
+                .await
+                .unwrap()
+                .is_active
+        );
+
+        payment_links.delete(link_id.clone()).await.unwrap();
+        assert!(payment_links.details(link_id.clone()).await.is_err());
+        assert!(payment_links.delete(link_id).await.is_err());
+    }
+}
@@ -0,0 +1,541 @@
+//! Maker-checker approval of the initiated payouts.
+//!
+//! A payout initiated with `requires_approval` (the default of IntaSend) waits for
+//! `PayoutsAPI::approve`. A [`PayoutApprovalPolicy`] decides when it may be approved:
+//!
+//! 1. payouts to beneficiaries outside of the known ones are blocked, as are the payouts
+//!    whose amount or beneficiaries are unknown,
+//! 2. approvals are held until the deadline of the policy (e.g. pay day),
+//! 3. payouts below the auto-approval threshold are approved right away,
+//! 4. the others wait for the approvals of `required_approvals` distinct checkers, the maker
+//!    (initiator) of the payout not being one of them.
+//!
+//! Every evaluation produces an [`ApprovalRecord`] to keep for audits, and
+//! `PayoutsAPI::approve_with_policy` only approves the payout when the record says so.
+//!
+//! ```rust
+//! # async fn run(payout_request: intasend::PayoutRequest) -> Result<(), Box<dyn std::error::Error>> {
+//! use intasend::payout_approval::{ApprovalDecision, PayoutApprovalPolicy, PendingPayout};
+//! use std::time::SystemTime;
+//!
+//! let intasend = intasend::Intasend::new(
+//!     std::env::var("INTASEND_PUBLIC_KEY")?,
+//!     std::env::var("INTASEND_SECRET_KEY")?,
+//!     true,
+//! );
+//! let policy = PayoutApprovalPolicy::new()
+//!     .auto_approve_below(rust_decimal::Decimal::new(10_000, 0))
+//!     .required_approvals(2)
+//!     .known_beneficiaries(["254712345678", "254723456789"]);
+//!
+//! let payout = intasend.payouts().initiate(payout_request).await?;
+//! let mut pending = PendingPayout::new(payout).made_by("alice");
+//! pending.approve("bob", SystemTime::now())?;
+//!
+//! let (record, approved) = intasend
+//!     .payouts()
+//!     .approve_with_policy(&pending, &policy, SystemTime::now())
+//!     .await?;
+//! println!("{}", serde_json::to_string(&record)?);
+//! if let ApprovalDecision::AwaitApprovals { required, received } = record.decision {
+//!     println!("{} more approval(s) needed", required - received);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::{Payout, PayoutApprovalRequest, PayoutsAPI};
+use crate::intasend::IntasendClientError;
+
+/// `CheckerApproval` struct - the approval of a payout by a checker
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CheckerApproval {
+    pub approver: String,
+    /// Approval time, in seconds since the UNIX epoch
+    pub approved_at: u64,
+}
+
+/// `PendingPayout` struct - an initiated payout and the approvals it collected
+#[derive(Clone, Debug)]
+pub struct PendingPayout {
+    payout: Payout,
+    maker: Option<String>,
+    approvals: Vec<CheckerApproval>,
+}
+
+impl PendingPayout {
+    /// Starts collecting the approvals of `payout`
+    pub fn new(payout: Payout) -> Self {
+        Self {
+            payout,
+            maker: None,
+            approvals: Vec::new(),
+        }
+    }
+
+    /// Sets the user who initiated the payout, who cannot approve it
+    pub fn made_by(mut self, maker: &str) -> Self {
+        self.maker = Some(maker.to_string());
+        self
+    }
+
+    /// Records the approval of `approver`, failing if they are the maker of the payout; a
+    /// second approval of the same checker is ignored
+    pub fn approve(&mut self, approver: &str, at: SystemTime) -> Result<(), IntasendClientError> {
+        if self.maker.as_deref() == Some(approver) {
+            return Err(IntasendClientError::Approval(format!(
+                "{} initiated the payout and cannot approve it",
+                approver
+            )));
+        }
+        if !self
+            .approvals
+            .iter()
+            .any(|approval| approval.approver == approver)
+        {
+            self.approvals.push(CheckerApproval {
+                approver: approver.to_string(),
+                approved_at: seconds(at),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn payout(&self) -> &Payout {
+        &self.payout
+    }
+
+    /// The user who initiated the payout
+    pub fn maker(&self) -> Option<&str> {
+        self.maker.as_deref()
+    }
+
+    /// The recorded approvals, in the order they were given
+    pub fn approvals(&self) -> &[CheckerApproval] {
+        &self.approvals
+    }
+
+    /// The number of distinct checkers who approved the payout, its maker excluded
+    pub fn approvers(&self) -> usize {
+        self.approvals
+            .iter()
+            .map(|approval| approval.approver.as_str())
+            .filter(|approver| self.maker.as_deref() != Some(*approver))
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+
+    /// The amount sent to the beneficiaries, fees excluded, `None` when the payout has
+    /// neither a total amount nor transactions
+    pub fn total(&self) -> Option<Decimal> {
+        self.payout.total_amount.or_else(|| {
+            self.payout
+                .transactions
+                .as_ref()
+                .filter(|transactions| !transactions.is_empty())
+                .map(|transactions| {
+                    transactions
+                        .iter()
+                        .map(|transaction| transaction.amount)
+                        .sum()
+                })
+        })
+    }
+}
+
+/// `ApprovalDecision` enum - what the policy allows for a payout
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    /// The payout may be approved
+    Approve,
+    /// The payout waits for more approvals
+    AwaitApprovals { required: usize, received: usize },
+    /// The payout may not be approved before `until`, in seconds since the UNIX epoch
+    Hold { until: u64 },
+    /// The payout may not be approved, it pays unknown beneficiaries (none when the amount
+    /// or the beneficiaries of the payout are unknown)
+    Block { unknown_accounts: Vec<String> },
+}
+
+/// `ApprovalRecord` struct - the auditable outcome of the evaluation of a payout
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ApprovalRecord {
+    pub tracking_id: Option<String>,
+    pub batch_reference: Option<String>,
+    /// The amount sent to the beneficiaries, `None` when unknown
+    pub total: Option<Decimal>,
+    pub transactions: usize,
+    pub maker: Option<String>,
+    pub approvals: Vec<CheckerApproval>,
+    pub decision: ApprovalDecision,
+    /// Why the policy took its decision
+    pub reason: String,
+    /// Evaluation time, in seconds since the UNIX epoch
+    pub decided_at: u64,
+}
+
+impl ApprovalRecord {
+    /// Whether the payout may be approved
+    pub fn is_approved(&self) -> bool {
+        self.decision == ApprovalDecision::Approve
+    }
+}
+
+/// `PayoutApprovalPolicy` struct - the rules deciding when a payout may be approved, see the
+/// module documentation
+#[derive(Clone, Debug)]
+pub struct PayoutApprovalPolicy {
+    auto_approve_below: Option<Decimal>,
+    required_approvals: usize,
+    known_beneficiaries: Option<BTreeSet<String>>,
+    hold_until: Option<SystemTime>,
+}
+
+impl Default for PayoutApprovalPolicy {
+    fn default() -> Self {
+        Self {
+            auto_approve_below: None,
+            required_approvals: 1,
+            known_beneficiaries: None,
+            hold_until: None,
+        }
+    }
+}
+
+impl PayoutApprovalPolicy {
+    /// Creates a policy requiring one approval for every payout
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Approves the payouts whose total is below `threshold` without any checker
+    pub fn auto_approve_below(mut self, threshold: Decimal) -> Self {
+        self.auto_approve_below = Some(threshold);
+        self
+    }
+
+    /// Sets the number of distinct checkers approving the other payouts
+    pub fn required_approvals(mut self, required_approvals: usize) -> Self {
+        self.required_approvals = required_approvals;
+        self
+    }
+
+    /// Blocks the payouts to accounts outside of `accounts`
+    pub fn known_beneficiaries<I, S>(mut self, accounts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.known_beneficiaries = Some(accounts.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Holds the approvals until `deadline`
+    pub fn hold_until(mut self, deadline: SystemTime) -> Self {
+        self.hold_until = Some(deadline);
+        self
+    }
+
+    /// Evaluates `pending` at `now`
+    pub fn evaluate(&self, pending: &PendingPayout, now: SystemTime) -> ApprovalRecord {
+        let total = pending.total();
+        let (decision, reason) = self.decide(pending, total, now);
+
+        ApprovalRecord {
+            tracking_id: pending.payout.tracking_id.clone(),
+            batch_reference: pending.payout.batch_reference.clone(),
+            total,
+            transactions: pending.payout.transactions.as_ref().map_or(0, Vec::len),
+            maker: pending.maker.clone(),
+            approvals: pending.approvals.clone(),
+            decision,
+            reason,
+            decided_at: seconds(now),
+        }
+    }
+
+    fn decide(
+        &self,
+        pending: &PendingPayout,
+        total: Option<Decimal>,
+        now: SystemTime,
+    ) -> (ApprovalDecision, String) {
+        let blocked = |reason: &str| {
+            (
+                ApprovalDecision::Block {
+                    unknown_accounts: Vec::new(),
+                },
+                reason.to_string(),
+            )
+        };
+        let Some(total) = total else {
+            return blocked("the payout has no total amount nor transactions");
+        };
+
+        if let Some(known) = &self.known_beneficiaries {
+            let Some(transactions) = pending
+                .payout
+                .transactions
+                .as_ref()
+                .filter(|transactions| !transactions.is_empty())
+            else {
+                return blocked("the payout has no transactions to check the beneficiaries of");
+            };
+            let unknown_accounts: Vec<String> = transactions
+                .iter()
+                .map(|transaction| transaction.account.clone())
+                .filter(|account| !known.contains(account))
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            if !unknown_accounts.is_empty() {
+                let reason = format!("unknown beneficiaries: {}", unknown_accounts.join(", "));
+                return (ApprovalDecision::Block { unknown_accounts }, reason);
+            }
+        }
+
+        if let Some(deadline) = self.hold_until.filter(|deadline| now < *deadline) {
+            let until = seconds(deadline);
+            return (
+                ApprovalDecision::Hold { until },
+                format!("approvals are held until {}", until),
+            );
+        }
+
+        if let Some(threshold) = self
+            .auto_approve_below
+            .filter(|threshold| total < *threshold)
+        {
+            return (
+                ApprovalDecision::Approve,
+                format!(
+                    "total {} is below the auto-approval threshold of {}",
+                    total, threshold
+                ),
+            );
+        }
+
+        let received = pending.approvers();
+        if received >= self.required_approvals {
+            (
+                ApprovalDecision::Approve,
+                format!(
+                    "approved by {} of {} required checkers",
+                    received, self.required_approvals
+                ),
+            )
+        } else {
+            (
+                ApprovalDecision::AwaitApprovals {
+                    required: self.required_approvals,
+                    received,
+                },
+                format!(
+                    "approved by {} of {} required checkers",
+                    received, self.required_approvals
+                ),
+            )
+        }
+    }
+}
+
+impl TryFrom<&Payout> for PayoutApprovalRequest {
+    type Error = IntasendClientError;
+
+    /// The approval of an initiated payout, from its tracking id, batch reference and nonce
+    fn try_from(payout: &Payout) -> Result<Self, Self::Error> {
+        let missing = |field: &str| {
+            IntasendClientError::Approval(format!("the payout has no {} to approve it", field))
+        };
+
+        Ok(Self {
+            tracking_id: payout
+                .tracking_id
+                .clone()
+                .ok_or_else(|| missing("tracking_id"))?,
+            batch_reference: payout.batch_reference.clone().unwrap_or_default(),
+            nonce: payout.nonce.clone().ok_or_else(|| missing("nonce"))?,
+            wallet: payout.wallet.clone(),
+            transactions: payout.transactions.clone(),
+        })
+    }
+}
+
+impl PayoutsAPI {
+    /// The `approve_with_policy` method evaluates `pending` with `policy` and approves the
+    /// payout if the policy allows it, returning the decision record and, when approved, the
+    /// updated payout
+    pub async fn approve_with_policy(
+        &self,
+        pending: &PendingPayout,
+        policy: &PayoutApprovalPolicy,
+        now: SystemTime,
+    ) -> Result<(ApprovalRecord, Option<Payout>)> {
+        let record = policy.evaluate(pending, now);
+        if !record.is_approved() {
+            return Ok((record, None));
+        }
+
+        let payout = self
+            .approve(PayoutApprovalRequest::try_from(&pending.payout)?)
+            .await?;
+        Ok((record, Some(payout)))
+    }
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intasend::payouts::PayoutResponseTransaction;
+    use rust_decimal_macros::dec;
+
+    const NOW: u64 = 1_704_179_402;
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    fn pending(amounts: &[(&str, Decimal)]) -> PendingPayout {
+        let transactions: Vec<PayoutResponseTransaction> = amounts
+            .iter()
+            .map(|(account, amount)| {
+                serde_json::from_value(serde_json::json!({
+                    "account": account,
+                    "amount": amount,
+                }))
+                .unwrap()
+            })
+            .collect();
+        let payout: Payout = serde_json::from_value(serde_json::json!({
+            "tracking_id": "T-1",
+            "batch_reference": "payroll-1",
+            "nonce": "N-1",
+            "transactions": transactions,
+        }))
+        .unwrap();
+        PendingPayout::new(payout).made_by("alice")
+    }
+
+    #[test]
+    fn large_payouts_wait_for_distinct_checkers() {
+        let policy = PayoutApprovalPolicy::new()
+            .auto_approve_below(dec!(1000))
+            .required_approvals(2);
+
+        let small = pending(&[("254712345678", dec!(999))]);
+        assert!(policy.evaluate(&small, at(NOW)).is_approved());
+
+        let mut large = pending(&[("254712345678", dec!(600)), ("254723456789", dec!(400))]);
+        assert!(large.approve("alice", at(NOW)).is_err());
+        large.approve("bob", at(NOW)).unwrap();
+        large.approve("bob", at(NOW + 1)).unwrap();
+        let record = policy.evaluate(&large, at(NOW));
+        assert_eq!(
+            record.decision,
+            ApprovalDecision::AwaitApprovals {
+                required: 2,
+                received: 1
+            }
+        );
+        assert_eq!(record.total, Some(dec!(1000)));
+        assert_eq!(record.transactions, 2);
+
+        large.approve("carol", at(NOW + 2)).unwrap();
+        let record = policy.evaluate(&large, at(NOW + 3));
+        assert!(record.is_approved());
+        assert_eq!(record.reason, "approved by 2 of 2 required checkers");
+        assert_eq!(record.decided_at, NOW + 3);
+    }
+
+    #[test]
+    fn unknown_beneficiaries_are_blocked_before_holds() {
+        let policy = PayoutApprovalPolicy::new()
+            .required_approvals(0)
+            .known_beneficiaries(["254712345678"])
+            .hold_until(at(NOW + 60));
+
+        let unknown = pending(&[("254712345678", dec!(10)), ("254799999999", dec!(10))]);
+        assert_eq!(
+            policy.evaluate(&unknown, at(NOW)).decision,
+            ApprovalDecision::Block {
+                unknown_accounts: vec!["254799999999".to_string()]
+            }
+        );
+
+        let known = pending(&[("254712345678", dec!(10))]);
+        assert_eq!(
+            policy.evaluate(&known, at(NOW)).decision,
+            ApprovalDecision::Hold { until: NOW + 60 }
+        );
+        assert!(policy.evaluate(&known, at(NOW + 60)).is_approved());
+    }
+
+    #[test]
+    fn makers_do_not_count_as_checkers() {
+        let policy = PayoutApprovalPolicy::new().required_approvals(1);
+        let payout = pending(&[("254712345678", dec!(10))]).payout().clone();
+
+        let mut pending = PendingPayout::new(payout);
+        pending.approve("alice", at(NOW)).unwrap();
+        let pending = pending.made_by("alice");
+        assert_eq!(pending.approvers(), 0);
+        assert_eq!(
+            policy.evaluate(&pending, at(NOW)).decision,
+            ApprovalDecision::AwaitApprovals {
+                required: 1,
+                received: 0
+            }
+        );
+    }
+
+    #[test]
+    fn payouts_without_amounts_or_beneficiaries_are_blocked() {
+        let policy = PayoutApprovalPolicy::new().auto_approve_below(dec!(1000));
+        let empty = pending(&[]);
+        assert_eq!(empty.total(), None);
+        let record = policy.evaluate(&empty, at(NOW));
+        assert_eq!(
+            record.decision,
+            ApprovalDecision::Block {
+                unknown_accounts: Vec::new()
+            }
+        );
+        assert_eq!(record.total, None);
+
+        let mut payout = empty.payout().clone();
+        payout.total_amount = Some(dec!(10));
+        let totalled = PendingPayout::new(payout);
+        assert!(policy.evaluate(&totalled, at(NOW)).is_approved());
+        assert!(!policy
+            .known_beneficiaries(["254712345678"])
+            .evaluate(&totalled, at(NOW))
+            .is_approved());
+    }
+
+    #[test]
+    fn records_serialize_their_decision() {
+        let record =
+            PayoutApprovalPolicy::new().evaluate(&pending(&[("254712345678", dec!(10))]), at(NOW));
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["decision"]["decision"], "await_approvals");
+        assert_eq!(json["decision"]["required"], 1);
+        assert_eq!(
+            serde_json::from_value::<ApprovalRecord>(json).unwrap(),
+            record
+        );
+    }
+}
@@ -0,0 +1,396 @@
+//! Splitting of large payouts into batches within the per-batch limits.
+//!
+//! A [`PayoutBatchPlanner`] splits the transactions of a `PayoutRequest` into batches of at
+//! most `max_transactions` transactions and, optionally, `max_total` in amount, keeping their
+//! order. A `budget`, e.g. the available balance of the wallet, caps the total of the whole
+//! payout.
+//!
+//! Each batch gets a deterministic `batch_reference` (`<reference>-<n>`), derived from the
+//! `batch_reference` of the request, or from the `run_id` of the planner and the content of
+//! the request when it has none: planning the same payout of the same run again gives the
+//! same references, so a resubmitted batch is recognized by the idempotency store of the
+//! client. The references must be unique to a run, a payroll paid every month needs a new
+//! `run_id` (e.g. `payroll-2025-01`) every month.
+//!
+//! `PayoutsAPI::initiate_batches` submits the batches and reports the tracking id of each
+//! initiated batch and the error of each failed one.
+//!
+//! ```rust
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! use intasend::payout_batch::PayoutBatchPlanner;
+//!
+//! let intasend = intasend::Intasend::new(
+//!     std::env::var("INTASEND_PUBLIC_KEY")?,
+//!     std::env::var("INTASEND_SECRET_KEY")?,
+//!     true,
+//! );
+//! let payroll = intasend::payout_import::PayoutImporter::new()
+//!     .from_path("payroll.csv")?
+//!     .into_requests()?;
+//!
+//! let balance = intasend.wallets().list().await?.results[0].available_balance;
+//! let planner = PayoutBatchPlanner::new(100)
+//!     .max_total(rust_decimal::Decimal::new(1_000_000, 0))
+//!     .budget(balance)
+//!     .run_id("payroll-2025-01");
+//! for request in payroll {
+//!     let batches = planner.plan(request)?;
+//!     let report = intasend.payouts().initiate_batches(batches, 1).await;
+//!     println!("[#] Initiated: {:?}", report.tracking_ids());
+//!     for failure in &report.failed {
+//!         eprintln!("[!] {}: {}", failure.batch_reference, failure.error);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::Error;
+use futures::stream::{self, StreamExt};
+use rust_decimal::Decimal;
+
+use super::{Payout, PayoutRequest, PayoutRequestTransaction, PayoutsAPI};
+use crate::intasend::IntasendClientError;
+
+/// `PayoutBatchPlanner` struct - splits payouts into batches within the per-batch limits
+#[derive(Clone, Debug)]
+pub struct PayoutBatchPlanner {
+    max_transactions: usize,
+    max_total: Option<Decimal>,
+    budget: Option<Decimal>,
+    run_id: Option<String>,
+}
+
+impl PayoutBatchPlanner {
+    /// Creates a planner of batches of at most `max_transactions` transactions
+    pub fn new(max_transactions: usize) -> Self {
+        Self {
+            max_transactions: max_transactions.max(1),
+            max_total: None,
+            budget: None,
+            run_id: None,
+        }
+    }
+
+    /// Limits the sum of the amounts of each batch to `max_total`
+    pub fn max_total(mut self, max_total: Decimal) -> Self {
+        self.max_total = Some(max_total);
+        self
+    }
+
+    /// Limits the sum of the amounts of all the batches to `budget`, e.g. the available
+    /// balance of the wallet
+    pub fn budget(mut self, budget: Decimal) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Sets the id of this run of the payouts, unique to the run (e.g. `payroll-2025-01`),
+    /// from which the references of the requests without `batch_reference` are derived
+    pub fn run_id(mut self, run_id: &str) -> Self {
+        self.run_id = Some(run_id.to_string());
+        self
+    }
+
+    /// Splits the transactions of `request` into batches sharing its other fields, failing
+    /// if a transaction alone exceeds `max_total`, if the request exceeds the `budget`, or if
+    /// it has neither a `batch_reference` nor a `run_id` to derive one from
+    pub fn plan(&self, request: PayoutRequest) -> Result<Vec<PayoutRequest>, IntasendClientError> {
+        let reference = match (&request.batch_reference, &self.run_id) {
+            (Some(reference), _) => reference.clone(),
+            (None, Some(run_id)) => content_reference(run_id, &request)?,
+            (None, None) => {
+                return Err(IntasendClientError::BatchPlan(
+                    "the payout has no batch_reference, set one or a run_id unique to this run"
+                        .to_string(),
+                ))
+            }
+        };
+
+        if let Some(budget) = self.budget {
+            let total: Decimal = request
+                .transactions
+                .iter()
+                .map(|transaction| transaction.amount)
+                .sum();
+            if total > budget {
+                return Err(IntasendClientError::BatchPlan(format!(
+                    "the payout total of {} exceeds the budget of {}",
+                    total, budget
+                )));
+            }
+        }
+
+        let mut batches: Vec<Vec<PayoutRequestTransaction>> = Vec::new();
+        let mut current: Vec<PayoutRequestTransaction> = Vec::new();
+        let mut total = Decimal::ZERO;
+        for transaction in &request.transactions {
+            if let Some(max_total) = self.max_total {
+                if transaction.amount > max_total {
+                    return Err(IntasendClientError::BatchPlan(format!(
+                        "the payout of {} to {} exceeds the batch total of {}",
+                        transaction.amount, transaction.account, max_total
+                    )));
+                }
+            }
+
+            let exceeds_total = self
+                .max_total
+                .is_some_and(|max_total| total + transaction.amount > max_total);
+            if !current.is_empty() && (current.len() == self.max_transactions || exceeds_total) {
+                batches.push(std::mem::take(&mut current));
+                total = Decimal::ZERO;
+            }
+            total += transaction.amount;
+            current.push(transaction.clone());
+        }
+        if !current.is_empty() {
+            batches.push(current);
+        }
+
+        Ok(batches
+            .into_iter()
+            .enumerate()
+            .map(|(index, transactions)| PayoutRequest {
+                batch_reference: Some(format!("{}-{}", reference, index + 1)),
+                transactions,
+                ..request.clone()
+            })
+            .collect())
+    }
+}
+
+/// `InitiatedBatch` struct - a batch accepted by IntaSend
+#[derive(Clone, Debug)]
+pub struct InitiatedBatch {
+    pub batch_reference: String,
+    pub payout: Payout,
+}
+
+/// `FailedBatch` struct - a batch IntaSend did not accept, with its transactions to retry
+#[derive(Debug)]
+pub struct FailedBatch {
+    pub batch_reference: String,
+    pub transactions: Vec<PayoutRequestTransaction>,
+    pub error: Error,
+}
+
+/// `PayoutBatchReport` struct - the outcome of the batches of a payout
+#[derive(Debug, Default)]
+pub struct PayoutBatchReport {
+    /// The initiated batches, in the order of the plan
+    pub initiated: Vec<InitiatedBatch>,
+    /// The failed batches, in the order of the plan
+    pub failed: Vec<FailedBatch>,
+}
+
+impl PayoutBatchReport {
+    /// The tracking ids of the initiated batches, to approve and follow them
+    pub fn tracking_ids(&self) -> Vec<String> {
+        self.initiated
+            .iter()
+            .filter_map(|batch| batch.payout.tracking_id.clone())
+            .collect()
+    }
+
+    /// Whether every batch was initiated
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// The sum of the amounts of the failed batches
+    pub fn failed_total(&self) -> Decimal {
+        self.failed
+            .iter()
+            .flat_map(|batch| &batch.transactions)
+            .map(|transaction| transaction.amount)
+            .sum()
+    }
+}
+
+impl PayoutsAPI {
+    /// The `initiate_batches` method initiates the batches of a `PayoutBatchPlanner`, at most
+    /// `concurrency` at a time (`1` submits them sequentially)
+    ///
+    /// A failed batch does not stop the others, see the `PayoutBatchReport`.
+    pub async fn initiate_batches(
+        &self,
+        batches: Vec<PayoutRequest>,
+        concurrency: usize,
+    ) -> PayoutBatchReport {
+        let outcomes: Vec<_> = stream::iter(batches)
+            .map(|batch| async move {
+                let batch_reference = batch.batch_reference.clone().unwrap_or_default();
+                let transactions = batch.transactions.clone();
+                (batch_reference, transactions, self.initiate(batch).await)
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await;
+
+        let mut report = PayoutBatchReport::default();
+        for (batch_reference, transactions, outcome) in outcomes {
+            match outcome {
+                Ok(payout) => report.initiated.push(InitiatedBatch {
+                    batch_reference,
+                    payout,
+                }),
+                Err(error) => report.failed.push(FailedBatch {
+                    batch_reference,
+                    transactions,
+                    error,
+                }),
+            }
+        }
+        report
+    }
+}
+
+/// A reference derived from `run_id` and the content of `request`, stable across processes
+/// and platforms.
+fn content_reference(run_id: &str, request: &PayoutRequest) -> Result<String, IntasendClientError> {
+    // FNV-1a, the std hashers are not stable across Rust releases
+    let content = serde_json::to_vec(request)?;
+    let hash = run_id
+        .as_bytes()
+        .iter()
+        .chain(&[0])
+        .chain(&content)
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+    Ok(format!("batch-{:016x}", hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intasend::fixtures::PayoutRequestBuilder;
+    use rust_decimal_macros::dec;
+
+    fn request(amounts: &[Decimal]) -> PayoutRequest {
+        PayoutRequestBuilder::new()
+            .batch_reference(Some("payroll"))
+            .amounts(amounts)
+            .build()
+    }
+
+    fn sizes(batches: &[PayoutRequest]) -> Vec<usize> {
+        batches
+            .iter()
+            .map(|batch| batch.transactions.len())
+            .collect()
+    }
+
+    #[test]
+    fn batches_respect_the_count_and_total_limits() {
+        let amounts = [dec!(100), dec!(200), dec!(300), dec!(400), dec!(50)];
+
+        let batches = PayoutBatchPlanner::new(2).plan(request(&amounts)).unwrap();
+        assert_eq!(sizes(&batches), [2, 2, 1]);
+
+        let batches = PayoutBatchPlanner::new(10)
+            .max_total(dec!(500))
+            .plan(request(&amounts))
+            .unwrap();
+        assert_eq!(sizes(&batches), [2, 1, 2]);
+        assert_eq!(batches[2].transactions[1].amount, dec!(50));
+
+        let error = PayoutBatchPlanner::new(10)
+            .max_total(dec!(250))
+            .plan(request(&amounts))
+            .unwrap_err();
+        assert!(matches!(error, IntasendClientError::BatchPlan(_)));
+        assert!(PayoutBatchPlanner::new(10)
+            .plan(request(&[]))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn payouts_over_budget_are_refused() {
+        let amounts = [dec!(100), dec!(200), dec!(300)];
+
+        let batches = PayoutBatchPlanner::new(1)
+            .budget(dec!(600))
+            .plan(request(&amounts))
+            .unwrap();
+        assert_eq!(sizes(&batches), [1, 1, 1]);
+
+        let error = PayoutBatchPlanner::new(1)
+            .max_total(dec!(300))
+            .budget(dec!(599))
+            .plan(request(&amounts))
+            .unwrap_err();
+        assert!(
+            matches!(error, IntasendClientError::BatchPlan(message) if message.contains("budget"))
+        );
+    }
+
+    #[test]
+    fn batch_references_are_deterministic_within_a_run() {
+        let amounts = [dec!(100), dec!(200), dec!(300)];
+        let unnamed = |amounts: &[Decimal]| PayoutRequestBuilder::new().amounts(amounts).build();
+        let references = |planner: &PayoutBatchPlanner, request: PayoutRequest| -> Vec<String> {
+            planner
+                .plan(request)
+                .unwrap()
+                .into_iter()
+                .map(|batch| batch.batch_reference.unwrap())
+                .collect()
+        };
+
+        let january = PayoutBatchPlanner::new(2).run_id("payroll-2025-01");
+        let first = references(&january, unnamed(&amounts));
+        assert_eq!(first, references(&january, unnamed(&amounts)));
+        assert_ne!(
+            first,
+            references(&january, unnamed(&[dec!(100), dec!(200), dec!(301)]))
+        );
+        assert!(first[0].starts_with("batch-") && first[0].ends_with("-1"));
+
+        let february = PayoutBatchPlanner::new(2).run_id("payroll-2025-02");
+        assert_ne!(first, references(&february, unnamed(&amounts)));
+
+        assert_eq!(
+            references(&february, request(&amounts)),
+            ["payroll-1", "payroll-2"]
+        );
+        assert!(matches!(
+            PayoutBatchPlanner::new(2).plan(unnamed(&amounts)),
+            Err(IntasendClientError::BatchPlan(_))
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod mock_server_tests {
+    use super::*;
+    use crate::intasend::fixtures::PayoutRequestBuilder;
+    use crate::testing::MockServer;
+    use rust_decimal_macros::dec;
+
+    #[tokio::test]
+    async fn large_payouts_are_initiated_in_batches() {
+        let server = MockServer::start();
+        let payouts = server.client().payouts();
+        let request = PayoutRequestBuilder::new()
+            .batch_reference(Some("payroll"))
+            .amounts(&[dec!(100), dec!(200), dec!(0), dec!(300), dec!(400)])
+            .build();
+
+        let batches = PayoutBatchPlanner::new(2).plan(request).unwrap();
+        let report = payouts.initiate_batches(batches, 2).await;
+
+        assert!(!report.is_complete());
+        assert_eq!(report.tracking_ids().len(), 2);
+        let references: Vec<&str> = report
+            .initiated
+            .iter()
+            .map(|batch| batch.batch_reference.as_str())
+            .collect();
+        assert_eq!(references, ["payroll-1", "payroll-3"]);
+        assert_eq!(report.failed[0].batch_reference, "payroll-2");
+        assert_eq!(report.failed_total(), dec!(300));
+    }
+}
@@ -0,0 +1,710 @@
+//! Bulk payouts from spreadsheets: CSV, JSON and JSON Lines files of recipients.
+//!
+//! Each row describes a payout transaction with the columns (or keys) `account` and
+//! `amount`, and optionally `name`, `bank_code`, `narrative`, `account_reference`,
+//! `id_number`, `category_name`, `account_type`, `provider` and `currency`. The column names
+//! are case-insensitive.
+//!
+//! The rows are validated one by one: a [`PayoutImport`] holds the `PayoutRequest`s of the
+//! valid rows, grouped by provider and currency, and an [`ImportRowError`] with the line
+//! number of every invalid row.
+//!
+//! ```rust
+//! use intasend::payout_import::PayoutImporter;
+//!
+//! let csv = "\
+//! name,account,amount,narrative
+//! Jane Doe,0712345678,1500,Salary
+//! John Doe,254723456789,\"2,000.50\",Salary
+//! Bad Row,,100,Salary
+//! ";
+//!
+//! let import = PayoutImporter::new().from_csv(csv.as_bytes())?;
+//! assert_eq!(import.rows, 3);
+//! assert_eq!(import.errors[0].to_string(), "line 4: account: is required");
+//!
+//! // Refuse the whole file when a row is invalid
+//! assert!(import.clone().into_requests().is_err());
+//! assert_eq!(import.requests[0].transactions.len(), 2);
+//! # Ok::<(), intasend::IntasendClientError>(())
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use rust_decimal::Decimal;
+use serde_json::Value as JSON;
+
+use super::{PayoutRequest, PayoutRequestTransaction};
+use crate::intasend::{Currency, IntasendClientError, PayoutProvider};
+
+/// `ImportRowError` struct - why a row of the file was rejected
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportRowError {
+    /// Line of the row in the file, starting at 1
+    pub line: u64,
+    /// Column of the invalid value, `None` when the whole row is invalid
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ImportRowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "line {}: {}: {}", self.line, field, self.message),
+            None => write!(f, "line {}: {}", self.line, self.message),
+        }
+    }
+}
+
+/// `PayoutImport` struct - the payout requests of the valid rows of a file and the errors of
+/// the invalid ones
+#[derive(Clone, Debug)]
+pub struct PayoutImport {
+    /// One request per provider and currency, in the order of their first row
+    pub requests: Vec<PayoutRequest>,
+    pub errors: Vec<ImportRowError>,
+    /// Number of rows read, valid or not
+    pub rows: usize,
+}
+
+impl PayoutImport {
+    /// Whether every row of the file is valid
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// The payout requests, failing if any row is invalid
+    pub fn into_requests(self) -> Result<Vec<PayoutRequest>, IntasendClientError> {
+        if self.errors.is_empty() {
+            return Ok(self.requests);
+        }
+
+        let errors: Vec<String> = self.errors.iter().map(ToString::to_string).collect();
+        Err(IntasendClientError::Import(format!(
+            "{} invalid row(s): {}",
+            errors.len(),
+            errors.join("; ")
+        )))
+    }
+}
+
+/// `PayoutImporter` struct - reads the payout files, see the module documentation
+#[derive(Clone, Debug)]
+pub struct PayoutImporter {
+    currency: Currency,
+    provider: Option<PayoutProvider>,
+    max_transactions: Option<usize>,
+}
+
+impl Default for PayoutImporter {
+    fn default() -> Self {
+        Self {
+            currency: Currency::Kes,
+            provider: None,
+            max_transactions: None,
+        }
+    }
+}
+
+impl PayoutImporter {
+    /// Creates an importer of KES payouts whose provider is `PESALINK` for the rows with a
+    /// `bank_code`, `MPESA-B2C` otherwise
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the currency of the rows without a `currency` column
+    pub fn currency(mut self, currency: Currency) -> Self {
+        self.currency = currency;
+        self
+    }
+
+    /// Sets the provider of the rows without a `provider` column
+    pub fn provider(mut self, provider: PayoutProvider) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Splits the requests having more than `max_transactions` transactions
+    pub fn max_transactions(mut self, max_transactions: usize) -> Self {
+        self.max_transactions = Some(max_transactions.max(1));
+        self
+    }
+
+    /// Reads the file at `path`, a CSV, JSON or JSON Lines (`.jsonl`, `.ndjson`) file
+    /// depending on its extension
+    pub fn from_path<P: AsRef<Path>>(&self, path: P) -> Result<PayoutImport, IntasendClientError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|error| {
+            IntasendClientError::Import(format!("{}: {}", path.display(), error))
+        })?;
+
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match extension.as_str() {
+            "csv" => self.from_csv(file),
+            "json" => self.from_json(file),
+            "jsonl" | "ndjson" => self.from_jsonl(file),
+            _ => Err(IntasendClientError::Import(format!(
+                "{}: unsupported file type, expected .csv, .json, .jsonl or .ndjson",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Reads a CSV file, whose first line names the columns
+    pub fn from_csv<R: Read>(&self, reader: R) -> Result<PayoutImport, IntasendClientError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let columns: Vec<String> = reader
+            .headers()
+            .map_err(|error| IntasendClientError::Import(error.to_string()))?
+            .iter()
+            .map(|column| column.trim_start_matches('\u{feff}').to_ascii_lowercase())
+            .collect();
+        for required in ["account", "amount"] {
+            if !columns.iter().any(|column| column == required) {
+                return Err(IntasendClientError::Import(format!(
+                    "missing the `{}` column",
+                    required
+                )));
+            }
+        }
+
+        let mut batch = Batch::new(self);
+        for record in reader.records() {
+            match record {
+                Ok(record) => {
+                    let line = record.position().map_or(0, |position| position.line());
+                    let row = columns
+                        .iter()
+                        .cloned()
+                        .zip(record.iter().map(str::to_string))
+                        .collect();
+                    batch.add(line, row);
+                }
+                Err(error) => {
+                    let line = error.position().map_or(0, |position| position.line());
+                    batch.reject(line, error.to_string());
+                }
+            }
+        }
+        Ok(batch.finish())
+    }
+
+    /// Reads a JSON file holding an array of objects
+    pub fn from_json<R: Read>(&self, reader: R) -> Result<PayoutImport, IntasendClientError> {
+        let text = read_text(reader)?;
+        let mut batch = Batch::new(self);
+
+        let mut position = skip_whitespace(&text, 0);
+        if !text[position..].starts_with('[') {
+            return Err(IntasendClientError::Import(
+                "expected a JSON array of objects".to_string(),
+            ));
+        }
+        position = skip_whitespace(&text, position + 1);
+        if text[position..].starts_with(']') {
+            return Ok(batch.finish());
+        }
+
+        loop {
+            // Each element is parsed on its own to know its line
+            let line = line_of(&text, position);
+            let mut elements =
+                serde_json::Deserializer::from_str(&text[position..]).into_iter::<JSON>();
+            match elements.next() {
+                Some(Ok(element)) => batch.add_json(line, element),
+                Some(Err(error)) => {
+                    return Err(IntasendClientError::Import(format!(
+                        "line {}: {}",
+                        line + error.line() as u64 - 1,
+                        error
+                    )))
+                }
+                None => break,
+            }
+            position = skip_whitespace(&text, position + elements.byte_offset());
+
+            match text[position..].chars().next() {
+                Some(',') => position = skip_whitespace(&text, position + 1),
+                Some(']') => break,
+                _ => {
+                    return Err(IntasendClientError::Import(format!(
+                        "line {}: expected `,` or `]` after an element",
+                        line_of(&text, position)
+                    )))
+                }
+            }
+        }
+        Ok(batch.finish())
+    }
+
+    /// Reads a JSON Lines file, one object per line
+    pub fn from_jsonl<R: Read>(&self, reader: R) -> Result<PayoutImport, IntasendClientError> {
+        let text = read_text(reader)?;
+        let mut batch = Batch::new(self);
+        for (index, content) in text.lines().enumerate() {
+            if content.trim().is_empty() {
+                continue;
+            }
+            let line = index as u64 + 1;
+            match serde_json::from_str::<JSON>(content) {
+                Ok(row) => batch.add_json(line, row),
+                Err(error) => batch.reject(line, error.to_string()),
+            }
+        }
+        Ok(batch.finish())
+    }
+}
+
+/// The requests being built from the rows of a file
+struct Batch<'a> {
+    importer: &'a PayoutImporter,
+    /// Requests with the serialized provider and currency they are grouped by
+    groups: Vec<(String, PayoutRequest)>,
+    errors: Vec<ImportRowError>,
+    rows: usize,
+}
+
+impl<'a> Batch<'a> {
+    fn new(importer: &'a PayoutImporter) -> Self {
+        Self {
+            importer,
+            groups: Vec::new(),
+            errors: Vec::new(),
+            rows: 0,
+        }
+    }
+
+    fn add_json(&mut self, line: u64, row: JSON) {
+        let JSON::Object(fields) = row else {
+            return self.reject(line, "expected an object".to_string());
+        };
+
+        let row = fields
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let value = match value {
+                    JSON::String(text) => text,
+                    JSON::Number(number) => number.to_string(),
+                    JSON::Bool(flag) => flag.to_string(),
+                    _ => return None,
+                };
+                Some((key.to_ascii_lowercase(), value.trim().to_string()))
+            })
+            .collect();
+        self.add(line, row);
+    }
+
+    fn add(&mut self, line: u64, row: HashMap<String, String>) {
+        self.rows += 1;
+        match validate(self.importer, line, &row) {
+            Ok((provider, currency, transaction)) => {
+                let key = format!("{:?}/{:?}", provider, currency);
+                match self.groups.iter_mut().find(|(group, _)| *group == key) {
+                    Some((_, request)) => request.transactions.push(transaction),
+                    None => self.groups.push((
+                        key,
+                        PayoutRequest {
+                            currency,
+                            provider: Some(provider),
+                            device_id: None,
+                            callback_url: None,
+                            batch_reference: None,
+                            requires_approval: None,
+                            transactions: vec![transaction],
+                        },
+                    )),
+                }
+            }
+            Err(errors) => self.errors.extend(errors),
+        }
+    }
+
+    fn reject(&mut self, line: u64, message: String) {
+        self.rows += 1;
+        self.errors.push(ImportRowError {
+            line,
+            field: None,
+            message,
+        });
+    }
+
+    fn finish(self) -> PayoutImport {
+        let max_transactions = self.importer.max_transactions.unwrap_or(usize::MAX);
+        let mut requests = Vec::new();
+        for (_, request) in self.groups {
+            for transactions in request.transactions.chunks(max_transactions) {
+                requests.push(PayoutRequest {
+                    transactions: transactions.to_vec(),
+                    ..request.clone()
+                });
+            }
+        }
+
+        PayoutImport {
+            requests,
+            errors: self.errors,
+            rows: self.rows,
+        }
+    }
+}
+
+/// Validates a row, returning its transaction or the errors of each of its invalid fields.
+fn validate(
+    importer: &PayoutImporter,
+    line: u64,
+    row: &HashMap<String, String>,
+) -> Result<(PayoutProvider, Currency, PayoutRequestTransaction), Vec<ImportRowError>> {
+    let mut errors = Vec::new();
+    let mut error = |field: &str, message: String| {
+        errors.push(ImportRowError {
+            line,
+            field: Some(field.to_string()),
+            message,
+        })
+    };
+    let value = |field: &str| {
+        row.get(field)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+
+    let bank_code = value("bank_code");
+    let provider = match value("provider") {
+        Some(provider) => parse_provider(&provider).unwrap_or_else(|| {
+            error("provider", format!("unknown provider `{}`", provider));
+            PayoutProvider::MpesaB2c
+        }),
+        None => match (&importer.provider, &bank_code) {
+            (Some(provider), _) => provider.clone(),
+            (None, Some(_)) => PayoutProvider::Pesalink,
+            (None, None) => PayoutProvider::MpesaB2c,
+        },
+    };
+    let currency = match value("currency") {
+        Some(currency) => parse_currency(&currency).unwrap_or_else(|| {
+            error("currency", format!("unknown currency `{}`", currency));
+            Currency::Kes
+        }),
+        None => importer.currency.clone(),
+    };
+
+    let account = match value("account") {
+        None => {
+            error("account", "is required".to_string());
+            String::new()
+        }
+        Some(account) => match provider {
+            PayoutProvider::MpesaB2c | PayoutProvider::Airtime => normalize_phone_number(&account)
+                .unwrap_or_else(|| {
+                    error(
+                        "account",
+                        format!("`{}` is not a Kenyan phone number", account),
+                    );
+                    account
+                }),
+            _ => account,
+        },
+    };
+
+    let amount = match value("amount") {
+        None => {
+            error("amount", "is required".to_string());
+            Decimal::ZERO
+        }
+        Some(amount) => match strip_thousands_separators(&amount).map(|amount| amount.parse()) {
+            None => {
+                error(
+                    "amount",
+                    format!("`{}` has misplaced thousands separators", amount),
+                );
+                Decimal::ZERO
+            }
+            Some(Ok(parsed)) if parsed <= Decimal::ZERO => {
+                error("amount", format!("`{}` is not positive", amount));
+                parsed
+            }
+            Some(Ok(parsed)) if parsed.normalize().scale() > 2 => {
+                error("amount", format!("`{}` has more than 2 decimals", amount));
+                parsed
+            }
+            Some(Ok(parsed)) => parsed,
+            Some(Err(_)) => {
+                error("amount", format!("`{}` is not a number", amount));
+                Decimal::ZERO
+            }
+        },
+    };
+
+    if matches!(provider, PayoutProvider::Pesalink) {
+        match &bank_code {
+            None => error("bank_code", "is required for PESALINK payouts".to_string()),
+            Some(code) if !code.chars().all(|character| character.is_ascii_digit()) => {
+                error("bank_code", format!("`{}` is not a bank code", code))
+            }
+            Some(_) => {}
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok((
+        provider,
+        currency,
+        PayoutRequestTransaction {
+            name: value("name"),
+            account,
+            id_number: value("id_number"),
+            amount,
+            bank_code,
+            category_name: value("category_name"),
+            narrative: value("narrative"),
+            account_type: value("account_type"),
+            account_reference: value("account_reference"),
+        },
+    ))
+}
+
+/// Parses a provider as spelled by the API (`MPESA-B2C`), case and separator insensitive.
+fn parse_provider(provider: &str) -> Option<PayoutProvider> {
+    let provider = provider.to_ascii_uppercase().replace(['_', ' '], "-");
+    serde_json::from_value(JSON::String(provider)).ok()
+}
+
+/// Parses an ISO 4217 currency code, case insensitive.
+fn parse_currency(currency: &str) -> Option<Currency> {
+    serde_json::from_value(JSON::String(currency.to_ascii_uppercase())).ok()
+}
+
+/// Removes the commas of an amount written with thousands separators (`25,000.00`), or
+/// returns `None` when a comma is not one (`1,5`, `1.500,00`).
+fn strip_thousands_separators(amount: &str) -> Option<String> {
+    if !amount.contains(',') {
+        return Some(amount.to_string());
+    }
+
+    let (integer, fraction) = match amount.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (amount, None),
+    };
+    let is_digits = |text: &str| text.bytes().all(|byte| byte.is_ascii_digit());
+    let mut groups = integer.split(',');
+    let well_formed = groups
+        .next()
+        .is_some_and(|first| (1..=3).contains(&first.len()) && is_digits(first))
+        && groups.all(|group| group.len() == 3 && is_digits(group))
+        && fraction.is_none_or(|fraction| !fraction.is_empty() && is_digits(fraction));
+
+    well_formed.then(|| amount.replace(',', ""))
+}
+
+/// Normalizes a Kenyan phone number to `2547XXXXXXXX`, accepting the local `07XXXXXXXX`
+/// form and the `7XXXXXXXX` form left by spreadsheets dropping the leading zero.
+fn normalize_phone_number(phone_number: &str) -> Option<String> {
+    let digits: String = phone_number
+        .chars()
+        .filter(|character| !matches!(character, ' ' | '-' | '+'))
+        .collect();
+    if !digits.chars().all(|character| character.is_ascii_digit()) {
+        return None;
+    }
+
+    let normalized = match digits.len() {
+        12 if digits.starts_with("254") => digits,
+        10 if digits.starts_with('0') => format!("254{}", &digits[1..]),
+        9 => format!("254{}", digits),
+        _ => return None,
+    };
+    matches!(&normalized[3..4], "7" | "1").then_some(normalized)
+}
+
+fn read_text<R: Read>(mut reader: R) -> Result<String, IntasendClientError> {
+    let mut text = String::new();
+    reader
+        .read_to_string(&mut text)
+        .map_err(|error| IntasendClientError::Import(error.to_string()))?;
+    Ok(text)
+}
+
+fn skip_whitespace(text: &str, position: usize) -> usize {
+    position + (text[position..].len() - text[position..].trim_start().len())
+}
+
+fn line_of(text: &str, position: usize) -> u64 {
+    text[..position].matches('\n').count() as u64 + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn messages(import: &PayoutImport) -> Vec<String> {
+        import.errors.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn csv_rows_are_validated_and_grouped() {
+        let csv = "\u{feff}Name,Account,Amount,Bank_Code,Narrative,Currency\n\
+                   Jane,0712345678,1500,,Salary,\n\
+                   Acme Ltd,1234567890,\"25,000.00\",2,Invoice 42,\n\
+                   John,254723456789,200.5,,Salary,kes\n\
+                   Bad,12345,0,,Salary,\n\
+                   Vendor,1234567890,10.005,ABC,Invoice 43,\n\
+                   Abroad,9876543210,99,3,Invoice 44,USD\n";
+
+        let import = PayoutImporter::new().from_csv(csv.as_bytes()).unwrap();
+        assert_eq!(import.rows, 6);
+        assert_eq!(
+            messages(&import),
+            [
+                "line 5: account: `12345` is not a Kenyan phone number",
+                "line 5: amount: `0` is not positive",
+                "line 6: amount: `10.005` has more than 2 decimals",
+                "line 6: bank_code: `ABC` is not a bank code",
+            ]
+        );
+
+        let groups: Vec<(Option<PayoutProvider>, Currency, usize)> = import
+            .requests
+            .iter()
+            .map(|request| {
+                (
+                    request.provider.clone(),
+                    request.currency.clone(),
+                    request.transactions.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            format!("{:?}", groups),
+            "[(Some(MpesaB2c), Kes, 2), (Some(Pesalink), Kes, 1), (Some(Pesalink), Usd, 1)]"
+        );
+        assert_eq!(import.requests[0].transactions[0].account, "254712345678");
+        assert_eq!(import.requests[1].transactions[0].amount, dec!(25000.00));
+        assert!(import.into_requests().is_err());
+    }
+
+    #[test]
+    fn json_rows_report_their_lines() {
+        let json = r#"[
+            {"name": "Jane", "account": "254712345678", "amount": 1500},
+            {
+                "name": "John",
+                "account": "254723456789",
+                "amount": "abc"
+            },
+            "not an object"
+        ]"#;
+
+        let import = PayoutImporter::new().from_json(json.as_bytes()).unwrap();
+        assert_eq!(import.rows, 3);
+        assert_eq!(
+            messages(&import),
+            [
+                "line 3: amount: `abc` is not a number",
+                "line 8: expected an object",
+            ]
+        );
+
+        assert!(PayoutImporter::new()
+            .from_json("[{\"account\": 1,}]".as_bytes())
+            .is_err());
+        assert_eq!(
+            PayoutImporter::new()
+                .from_json(" [ ] ".as_bytes())
+                .unwrap()
+                .rows,
+            0
+        );
+    }
+
+    #[test]
+    fn jsonl_rows_are_split_in_requests() {
+        let jsonl = "{\"account\": \"0712345678\", \"amount\": 100, \"provider\": \"airtime\"}\n\
+                     \n\
+                     {\"account\": \"0723456789\", \"amount\": 100, \"provider\": \"airtime\"}\n\
+                     {\"account\": \"0734567890\", \"amount\": 100, \"provider\": \"airtime\"}\n\
+                     {broken\n";
+
+        let import = PayoutImporter::new()
+            .max_transactions(2)
+            .from_jsonl(jsonl.as_bytes())
+            .unwrap();
+        assert_eq!(import.rows, 4);
+        assert_eq!(import.errors.len(), 1);
+        assert_eq!(import.errors[0].line, 5);
+        assert_eq!(
+            import
+                .requests
+                .iter()
+                .map(|request| request.transactions.len())
+                .collect::<Vec<_>>(),
+            [2, 1]
+        );
+        assert!(matches!(
+            import.requests[0].provider,
+            Some(PayoutProvider::Airtime)
+        ));
+    }
+
+    #[test]
+    fn commas_must_be_thousands_separators() {
+        for (amount, stripped) in [
+            ("2,000.50", "2000.50"),
+            ("1,234,567", "1234567"),
+            ("999", "999"),
+        ] {
+            assert_eq!(
+                strip_thousands_separators(amount).as_deref(),
+                Some(stripped)
+            );
+        }
+        for amount in ["1,5", "1.500,00", ",500", "1,50,000", "1,000.", "12345,000"] {
+            assert_eq!(strip_thousands_separators(amount), None);
+        }
+
+        let csv = "Account,Amount\n0712345678,\"1,5\"\n0712345678,\"2,000.50\"\n";
+        let import = PayoutImporter::new().from_csv(csv.as_bytes()).unwrap();
+        assert_eq!(
+            messages(&import),
+            ["line 2: amount: `1,5` has misplaced thousands separators"]
+        );
+        assert_eq!(import.requests[0].transactions[0].amount, dec!(2000.50));
+    }
+
+    #[test]
+    fn phone_numbers_are_normalized() {
+        for phone_number in [
+            "254712345678",
+            "+254 712 345 678",
+            "0712345678",
+            "712345678",
+        ] {
+            assert_eq!(
+                normalize_phone_number(phone_number).as_deref(),
+                Some("254712345678")
+            );
+        }
+        assert_eq!(
+            normalize_phone_number("0110345678").as_deref(),
+            Some("254110345678")
+        );
+        for phone_number in ["12345", "0812345678", "07123456ab"] {
+            assert_eq!(normalize_phone_number(phone_number), None);
+        }
+    }
+}
@@ -5,10 +5,17 @@ use anyhow::{Error, Result};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
-use crate::{Intasend, Wallet};
+use super::wallets::Wallet;
+use crate::Intasend;
 
+use super::idempotency::generate_reference;
 use super::{Currency, PayoutProvider, RequestClient, RequestMethods};
 
+pub mod approval;
+pub mod batch;
+#[cfg(feature = "server")]
+pub mod import;
+
 /// `PayoutsAPI` struct implements methods for facilitating:
 /// Sending of funds to different recipients programatically.
 ///
@@ -39,17 +46,32 @@ impl PayoutsAPI {
     /// The `initiate` method initiates the API requests to IntaSend's `Payouts` (Send Money) API.
     /// This depends on the payload struct (`PayoutRequest`) passed into the method as arguments.
     /// This method is used under the hood to power all initiated requests depending on PayoutProvider.
+    /// A `batch_reference` is generated when the payload has none, it is the idempotency key of the payout.
     /// It returns a Result of Payout.
-    pub async fn initiate(&self, payload: PayoutRequest) -> Result<Payout> {
+    pub async fn initiate(&self, mut payload: PayoutRequest) -> Result<Payout> {
         let service_path: &str = "/api/v1/send-money/initiate/";
         let request_method = RequestMethods::Post;
 
-        let payout = self
-            .intasend
-            .send::<PayoutRequest, Payout>(Some(payload), service_path, request_method)
-            .await?;
-
-        Ok(payout)
+        let batch_reference = payload
+            .batch_reference
+            .get_or_insert_with(generate_reference)
+            .clone();
+
+        self.intasend
+            .idempotent(format!("payout:{}", batch_reference), || async {
+                #[cfg(feature = "metrics")]
+                let metered_payload = payload.clone();
+
+                let payout = self
+                    .intasend
+                    .send::<PayoutRequest, Payout>(Some(payload), service_path, request_method)
+                    .await?;
+                #[cfg(feature = "metrics")]
+                super::telemetry::record_payout(&metered_payload);
+
+                Ok(payout)
+            })
+            .await
     }
 
     /// The `mpesa_b2c` method initates Mpesa B2C payout API requests to IntaSend's `Payouts` API.
@@ -168,7 +190,7 @@ impl PayoutsAPI {
 }
 
 /// `Payout` struct
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct Payout {
     pub file_id: Option<String>,
     pub device_id: Option<String>,
@@ -227,7 +249,9 @@ pub struct PayoutRequest {
     pub device_id: Option<String>,
     pub callback_url: Option<String>,
     pub batch_reference: Option<String>,
-    //pub requires_approval: PayoutApproval,
+    /// Whether the payout waits for `PayoutsAPI::approve`, `YES` when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requires_approval: Option<PayoutApproval>,
     pub transactions: Vec<PayoutRequestTransaction>,
 }
 
@@ -258,7 +282,7 @@ pub struct PayoutCancelRequest {
 pub struct PayoutBankCodesRequest {}
 
 /// `PayoutApproval` enum
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum PayoutApproval {
     Yes,
@@ -0,0 +1,439 @@
+//! Local rendering of payment URLs as QR codes, for printed receipts and POS screens.
+//!
+//! `PaymentLink::qr_code` and `CheckoutResponse::qr_code` start a [`QrCode`] of their URL,
+//! rendered as SVG or PNG without calling IntaSend.
+
+use std::convert::Infallible;
+
+use embedded_graphics::mono_font::iso_8859_1::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, OriginDimensions, Point, Size};
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::{Drawable, Pixel};
+use qrcode::{Color, EcLevel};
+
+use super::checkout::CheckoutResponse;
+#[cfg(feature = "server")]
+use super::payment_links::PaymentLink;
+use super::IntasendClientError;
+
+/// Width of the light border around the code, in modules, as required by the QR code spec
+const QUIET_ZONE: usize = 4;
+
+/// Largest width of a rendered code, in pixels
+const MAX_SIZE: u32 = 4096;
+
+/// `QrErrorCorrection` enum - the share of the code that can be damaged and still be read
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QrErrorCorrection {
+    /// About 7%
+    Low,
+    /// About 15%
+    #[default]
+    Medium,
+    /// About 25%
+    Quartile,
+    /// About 30%, e.g. for codes printed with a logo or on thermal paper
+    High,
+}
+
+impl From<QrErrorCorrection> for EcLevel {
+    fn from(level: QrErrorCorrection) -> Self {
+        match level {
+            QrErrorCorrection::Low => EcLevel::L,
+            QrErrorCorrection::Medium => EcLevel::M,
+            QrErrorCorrection::Quartile => EcLevel::Q,
+            QrErrorCorrection::High => EcLevel::H,
+        }
+    }
+}
+
+/// `QrCode` struct - renders a URL as a QR code, with an optional caption below it
+///
+/// The code is `size` pixels wide, quiet zone included; the caption adds a band below it.
+///
+/// ```rust
+/// let qr_code = intasend::QrCode::new("https://sandbox.intasend.com/pay/8d7f60c4/")
+///     .size(320)
+///     .error_correction(intasend::QrErrorCorrection::High)
+///     .caption("Scan to pay KES 1,500");
+///
+/// let svg: String = qr_code.to_svg()?;
+/// let png: Vec<u8> = qr_code.to_png()?;
+/// assert!(svg.contains("Scan to pay KES 1,500"));
+/// assert!(png.starts_with(b"\x89PNG"));
+/// # Ok::<(), intasend::IntasendClientError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct QrCode {
+    data: String,
+    size: u32,
+    error_correction: QrErrorCorrection,
+    caption: Option<String>,
+}
+
+impl QrCode {
+    /// Creates the QR code of `data`, 256 pixels wide with medium error correction
+    pub fn new(data: &str) -> Self {
+        Self {
+            data: data.to_string(),
+            size: 256,
+            error_correction: QrErrorCorrection::default(),
+            caption: None,
+        }
+    }
+
+    /// Sets the width of the code in pixels, raised to one pixel per module if smaller.
+    /// Rendering a code wider than 4096 pixels fails with `IntasendClientError::QrCode`.
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Sets the error correction level, `Medium` by default
+    pub fn error_correction(mut self, error_correction: QrErrorCorrection) -> Self {
+        self.error_correction = error_correction;
+        self
+    }
+
+    /// Sets the caption written below the code, e.g. the amount or the merchant name
+    pub fn caption(mut self, caption: &str) -> Self {
+        self.caption = Some(caption.to_string());
+        self
+    }
+
+    /// Renders the code as an SVG document
+    pub fn to_svg(&self) -> Result<String, IntasendClientError> {
+        let layout = self.layout()?;
+        let module = layout.module as usize;
+        let (width, height) = (layout.width as usize, layout.height() as usize);
+
+        let mut path = String::new();
+        for (y, row) in layout.modules.chunks(layout.modules_width).enumerate() {
+            for (x, color) in row.iter().enumerate() {
+                if *color == Color::Dark {
+                    path.push_str(&format!(
+                        "M{} {}h{}v{}h-{}z",
+                        layout.offset as usize + x * module,
+                        layout.offset as usize + y * module,
+                        module,
+                        module,
+                        module
+                    ));
+                }
+            }
+        }
+
+        let mut svg = format!(
+            concat!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" ",
+                "viewBox=\"0 0 {0} {1}\" shape-rendering=\"crispEdges\">\n",
+                "<rect width=\"{0}\" height=\"{1}\" fill=\"#fff\"/>\n",
+                "<path d=\"{2}\" fill=\"#000\"/>\n"
+            ),
+            width, height, path
+        );
+        if let Some(caption) = &self.caption {
+            let font_size = layout.caption_height() / 2;
+            svg.push_str(&format!(
+                concat!(
+                    "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\" ",
+                    "text-anchor=\"middle\" dominant-baseline=\"middle\" fill=\"#000\">{}</text>\n"
+                ),
+                width / 2,
+                layout.width + layout.caption_height() / 2,
+                font_size,
+                escape_xml(caption)
+            ));
+        }
+        svg.push_str("</svg>\n");
+
+        Ok(svg)
+    }
+
+    /// Renders the code as an 8-bit grayscale PNG image
+    pub fn to_png(&self) -> Result<Vec<u8>, IntasendClientError> {
+        let layout = self.layout()?;
+        let mut canvas = Canvas::new(layout.width, layout.height())?;
+
+        let module = layout.module;
+        for (y, row) in layout.modules.chunks(layout.modules_width).enumerate() {
+            for (x, color) in row.iter().enumerate() {
+                if *color == Color::Dark {
+                    canvas.fill(
+                        layout.offset + x as u32 * module,
+                        layout.offset + y as u32 * module,
+                        module,
+                        module,
+                    );
+                }
+            }
+        }
+        if let Some(caption) = &self.caption {
+            canvas.write(caption, layout.width, layout.caption_height())?;
+        }
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, canvas.width, canvas.height);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&canvas.pixels))
+            .map_err(|error| IntasendClientError::QrCode(error.into()))?;
+
+        Ok(png)
+    }
+
+    fn layout(&self) -> Result<Layout, IntasendClientError> {
+        if self.size > MAX_SIZE {
+            return Err(IntasendClientError::QrCode(
+                format!(
+                    "QR code size {} exceeds the maximum of {} pixels",
+                    self.size, MAX_SIZE
+                )
+                .into(),
+            ));
+        }
+
+        let code =
+            qrcode::QrCode::with_error_correction_level(&self.data, self.error_correction.into())
+                .map_err(|error| IntasendClientError::QrCode(error.into()))?;
+
+        let modules_width = code.width();
+        let total_modules = (modules_width + 2 * QUIET_ZONE) as u32;
+        let width = self.size.max(total_modules);
+        let module = width / total_modules;
+
+        Ok(Layout {
+            modules: code.to_colors(),
+            modules_width,
+            module,
+            offset: (width - module * modules_width as u32) / 2,
+            width,
+            caption: self.caption.is_some(),
+        })
+    }
+}
+
+/// Placement of the modules in the rendered image
+struct Layout {
+    modules: Vec<Color>,
+    modules_width: usize,
+    /// Side of a module, in pixels
+    module: u32,
+    /// Position of the first module, in pixels from the top left corner
+    offset: u32,
+    /// Width (and height, caption excluded) of the image, in pixels
+    width: u32,
+    caption: bool,
+}
+
+impl Layout {
+    fn caption_height(&self) -> u32 {
+        if self.caption {
+            (self.width / 8).max(FONT_6X10.character_size.height + 4)
+        } else {
+            0
+        }
+    }
+
+    fn height(&self) -> u32 {
+        self.width + self.caption_height()
+    }
+}
+
+/// A white grayscale image, drawn in black
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Result<Self, IntasendClientError> {
+        let pixels = (width as usize)
+            .checked_mul(height as usize)
+            .filter(|_| width <= MAX_SIZE && height <= 2 * MAX_SIZE)
+            .ok_or_else(|| {
+                IntasendClientError::QrCode(
+                    format!("QR code image of {width}x{height} pixels is too large").into(),
+                )
+            })?;
+
+        Ok(Self {
+            width,
+            height,
+            pixels: vec![u8::MAX; pixels],
+        })
+    }
+
+    fn fill(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        for row in y..(y + height).min(self.height) {
+            let start = (row * self.width + x) as usize;
+            let end = (row * self.width + (x + width).min(self.width)) as usize;
+            self.pixels[start..end].fill(0);
+        }
+    }
+
+    /// Writes `caption` centered in the band of `band_height` pixels starting at `top`,
+    /// scaling the bitmap font up to half the band and cutting the text that does not fit.
+    fn write(
+        &mut self,
+        caption: &str,
+        top: u32,
+        band_height: u32,
+    ) -> Result<(), IntasendClientError> {
+        let glyph = FONT_6X10.character_size;
+        let max_chars = (self.width / glyph.width).max(1) as usize;
+        let mut text = caption.to_string();
+        if text.chars().count() > max_chars {
+            text = text.chars().take(max_chars.saturating_sub(3)).collect();
+            text.push_str("...");
+        }
+        let chars = text.chars().count().max(1) as u32;
+
+        let mut glyphs = Canvas::new(chars * glyph.width, glyph.height)?;
+        let _ = Text::with_baseline(
+            &text,
+            Point::zero(),
+            MonoTextStyle::new(&FONT_6X10, BinaryColor::On),
+            Baseline::Top,
+        )
+        .draw(&mut glyphs);
+
+        let scale = (self.width / glyphs.width)
+            .min(band_height / 2 / glyph.height)
+            .max(1);
+        let left = self.width.saturating_sub(glyphs.width * scale) / 2;
+        let top = top + band_height.saturating_sub(glyphs.height * scale) / 2;
+        for y in 0..glyphs.height {
+            for x in 0..glyphs.width {
+                if glyphs.pixels[(y * glyphs.width + x) as usize] == 0 {
+                    self.fill(left + x * scale, top + y * scale, scale, scale);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl OriginDimensions for Canvas {
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
+
+impl DrawTarget for Canvas {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if color.is_on()
+                && (0..self.width as i32).contains(&point.x)
+                && (0..self.height as i32).contains(&point.y)
+            {
+                self.fill(point.x as u32, point.y as u32, 1, 1);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Escapes `text` for XML text content.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(feature = "server")]
+impl PaymentLink {
+    /// The `qr_code` method starts the QR code of the payment link's URL, see `QrCode`
+    pub fn qr_code(&self) -> QrCode {
+        QrCode::new(&self.url)
+    }
+}
+
+impl CheckoutResponse {
+    /// The `qr_code` method starts the QR code of the checkout's URL, see `QrCode`
+    pub fn qr_code(&self) -> QrCode {
+        QrCode::new(&self.url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://sandbox.intasend.com/pay/8d7f60c4-1d2e-4f3a-9b8c-7d6e5f4a3b2c/";
+
+    fn png_size(png: &[u8]) -> (u32, u32) {
+        let reader = png::Decoder::new(png).read_info().unwrap();
+        (reader.info().width, reader.info().height)
+    }
+
+    #[test]
+    fn renders_the_code_at_the_requested_size() {
+        let qr_code = QrCode::new(URL).size(300);
+        assert_eq!(png_size(&qr_code.to_png().unwrap()), (300, 300));
+        assert!(qr_code
+            .to_svg()
+            .unwrap()
+            .starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"300\" height=\"300\""));
+
+        // Too small for the modules, one pixel per module
+        let (width, height) = png_size(&QrCode::new(URL).size(10).to_png().unwrap());
+        assert_eq!(width, height);
+        assert!(width > 10);
+    }
+
+    #[test]
+    fn captions_are_rendered_below_the_code() {
+        let qr_code = QrCode::new(URL)
+            .size(320)
+            .error_correction(QrErrorCorrection::High)
+            .caption("<Pay> KES 1,500 & more");
+        assert_eq!(png_size(&qr_code.to_png().unwrap()), (320, 360));
+
+        let svg = qr_code.to_svg().unwrap();
+        assert!(svg.contains(">&lt;Pay&gt; KES 1,500 &amp; more</text>"));
+        assert!(svg.contains("height=\"360\""));
+    }
+
+    #[test]
+    fn higher_error_correction_needs_more_modules() {
+        let modules = |level| {
+            QrCode::new(URL)
+                .error_correction(level)
+                .layout()
+                .unwrap()
+                .modules_width
+        };
+        assert!(modules(QrErrorCorrection::High) > modules(QrErrorCorrection::Low));
+    }
+
+    #[test]
+    fn oversized_codes_are_an_error() {
+        for size in [MAX_SIZE + 1, u32::MAX] {
+            let error = QrCode::new(URL).size(size).to_png().unwrap_err();
+            assert!(matches!(error, IntasendClientError::QrCode(_)));
+            assert!(QrCode::new(URL).size(size).to_svg().is_err());
+        }
+        let (width, _) = png_size(&QrCode::new(URL).size(MAX_SIZE).to_png().unwrap());
+        assert_eq!(width, MAX_SIZE);
+    }
+
+    #[test]
+    fn oversized_data_is_an_error() {
+        let error = QrCode::new(&"x".repeat(8000)).to_png().unwrap_err();
+        assert!(matches!(error, IntasendClientError::QrCode(_)));
+    }
+}
@@ -0,0 +1,304 @@
+//! Client side rate limiting for the `Intasend` client.
+//!
+//! A [`RateLimiter`] combines a token bucket [`RateLimit`] with a cap on the number of
+//! requests in flight, and can retry the requests IntaSend throttled (HTTP 429). The
+//! limiter is shared by every clone of the client and by all the `*API` handles created
+//! from it, so batch jobs fanning out `PayoutsAPI::status` or `CollectionsAPI::status`
+//! calls stay under the limits as a whole.
+//!
+//! Endpoints can get their own rate limit, matched by path prefix (the longest prefix
+//! wins); the requests of an overridden endpoint only use the override's bucket.
+//!
+//! ```rust
+//! use intasend::rate_limit::{RateLimit, RateLimiter};
+//!
+//! let limiter = RateLimiter::new(RateLimit::per_second(10))
+//!     .max_in_flight(4)
+//!     .endpoint("/api/v1/send-money/status/", RateLimit::per_second(2))
+//!     .retry_throttled(3);
+//!
+//! let intasend = intasend::Intasend::new(
+//!     "publishable_key".to_string(),
+//!     "secret_key".to_string(),
+//!     true,
+//! )
+//! .with_rate_limiter(limiter);
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_lock::{Semaphore, SemaphoreGuardArc};
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+
+use super::runtime::Runtime;
+use super::ApiResponse;
+
+/// Backoff of the first retry of a throttled request without `Retry-After`, doubled at
+/// every attempt
+const THROTTLED_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Longest wait between the retries of a throttled request, `Retry-After` included
+const MAX_THROTTLED_BACKOFF: Duration = Duration::from_secs(30);
+
+/// `RateLimit` struct - a token bucket refilled with `requests` tokens every `per` period
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    requests: u32,
+    per: Duration,
+    burst: u32,
+}
+
+impl RateLimit {
+    /// Allows `requests` requests every `per` period, with bursts of up to `requests` requests.
+    pub fn new(requests: u32, per: Duration) -> Self {
+        let requests = requests.max(1);
+        Self {
+            requests,
+            per,
+            burst: requests,
+        }
+    }
+
+    /// Allows `requests` requests per second
+    pub fn per_second(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(1))
+    }
+
+    /// Allows `requests` requests per minute
+    pub fn per_minute(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(60))
+    }
+
+    /// Sets the maximum number of requests that can be sent at once after an idle period
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    /// The time it takes to refill a single token
+    fn interval(&self) -> Duration {
+        self.per / self.requests
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    limit: RateLimit,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            state: Mutex::new((f64::from(limit.burst), Instant::now())),
+        }
+    }
+
+    /// Takes a token, or returns how long to wait for the next one.
+    fn try_take(&self) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap_or_else(|error| error.into_inner());
+        let (tokens, refilled_at) = &mut *state;
+
+        let now = Instant::now();
+        let interval = self.limit.interval().as_secs_f64();
+        let refill = now.duration_since(*refilled_at).as_secs_f64() / interval;
+        *tokens = (*tokens + refill).min(f64::from(self.limit.burst));
+        *refilled_at = now;
+
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - *tokens) * interval))
+        }
+    }
+
+    async fn take(&self, runtime: &dyn Runtime) {
+        while let Err(wait) = self.try_take() {
+            runtime.sleep(wait).await;
+        }
+    }
+}
+
+/// `RateLimiter` struct - client side rate limit, concurrency cap and throttling retries
+#[derive(Debug)]
+pub struct RateLimiter {
+    default: Option<Bucket>,
+    endpoints: Vec<(String, Bucket)>,
+    in_flight: Option<Arc<Semaphore>>,
+    throttled_retries: u32,
+}
+
+impl RateLimiter {
+    /// Creates a limiter applying `limit` to all the requests
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            default: Some(Bucket::new(limit)),
+            ..Self::unlimited()
+        }
+    }
+
+    /// Creates a limiter without a default rate limit, e.g. to only cap the requests in
+    /// flight or to only limit some endpoints
+    pub fn unlimited() -> Self {
+        Self {
+            default: None,
+            endpoints: Vec::new(),
+            in_flight: None,
+            throttled_retries: 0,
+        }
+    }
+
+    /// Caps the number of requests in flight at any time
+    pub fn max_in_flight(mut self, requests: usize) -> Self {
+        self.in_flight = Some(Arc::new(Semaphore::new(requests.max(1))));
+        self
+    }
+
+    /// Applies `limit` instead of the default one to the paths starting with `path_prefix`,
+    /// e.g. `/api/v1/payment/status/`
+    pub fn endpoint(mut self, path_prefix: &str, limit: RateLimit) -> Self {
+        self.endpoints
+            .push((path_prefix.to_string(), Bucket::new(limit)));
+        self
+    }
+
+    /// Retries the requests throttled by IntaSend (HTTP 429) up to `retries` times, waiting
+    /// for the `Retry-After` delay when there is one, or an exponential backoff otherwise, at
+    /// most 30 seconds either way
+    pub fn retry_throttled(mut self, retries: u32) -> Self {
+        self.throttled_retries = retries;
+        self
+    }
+
+    /// Waits until the request to `path` can be sent. The returned guard holds the
+    /// in-flight slot until the request completes.
+    pub(crate) async fn acquire(
+        &self,
+        path: &str,
+        runtime: &dyn Runtime,
+    ) -> Option<SemaphoreGuardArc> {
+        let endpoint = self
+            .endpoints
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, bucket)| bucket);
+
+        if let Some(bucket) = endpoint.or(self.default.as_ref()) {
+            bucket.take(runtime).await;
+        }
+
+        match &self.in_flight {
+            Some(semaphore) => Some(semaphore.acquire_arc().await),
+            None => None,
+        }
+    }
+
+    /// How long to wait before retrying `response`, if it was throttled and `attempt`
+    /// still allows a retry.
+    pub(crate) fn retry_delay(&self, response: &ApiResponse, attempt: u32) -> Option<Duration> {
+        if response.status != StatusCode::TOO_MANY_REQUESTS || attempt > self.throttled_retries {
+            return None;
+        }
+
+        let retry_after = response
+            .headers
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map(|seconds| Duration::from_secs(seconds).min(MAX_THROTTLED_BACKOFF));
+
+        Some(retry_after.unwrap_or_else(|| {
+            2u32.checked_pow(attempt.saturating_sub(1))
+                .and_then(|factor| THROTTLED_BACKOFF.checked_mul(factor))
+                .map_or(MAX_THROTTLED_BACKOFF, |backoff| {
+                    backoff.min(MAX_THROTTLED_BACKOFF)
+                })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::StdRuntime;
+    use reqwest::header::HeaderMap;
+
+    #[test]
+    fn bucket_spaces_requests_after_the_burst() {
+        let bucket = Bucket::new(RateLimit::per_second(20).burst(2));
+
+        assert!(bucket.try_take().is_ok());
+        assert!(bucket.try_take().is_ok());
+        let wait = bucket.try_take().unwrap_err();
+        assert!(wait <= Duration::from_millis(50));
+
+        let started = Instant::now();
+        futures::executor::block_on(bucket.take(&StdRuntime));
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn endpoint_overrides_use_their_own_bucket() {
+        let limiter = RateLimiter::new(RateLimit::per_minute(1))
+            .endpoint("/api/v1/send-money/", RateLimit::per_minute(1))
+            .endpoint("/api/v1/send-money/status/", RateLimit::per_minute(2));
+
+        let acquire = |path| futures::executor::block_on(limiter.acquire(path, &StdRuntime));
+        acquire("/api/v1/wallets/");
+        acquire("/api/v1/send-money/initiate/");
+        acquire("/api/v1/send-money/status/");
+        acquire("/api/v1/send-money/status/");
+
+        assert!(limiter.default.as_ref().unwrap().try_take().is_err());
+        assert!(limiter.endpoints[0].1.try_take().is_err());
+        assert!(limiter.endpoints[1].1.try_take().is_err());
+    }
+
+    #[test]
+    fn only_throttled_responses_are_retried() {
+        let limiter = RateLimiter::unlimited().retry_throttled(2);
+        let mut response = ApiResponse {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+        };
+
+        assert_eq!(
+            limiter.retry_delay(&response, 2),
+            Some(Duration::from_millis(1000))
+        );
+        assert_eq!(limiter.retry_delay(&response, 3), None);
+
+        let limiter = RateLimiter::unlimited().retry_throttled(u32::MAX);
+        assert_eq!(
+            limiter.retry_delay(&response, 7),
+            Some(MAX_THROTTLED_BACKOFF)
+        );
+        assert_eq!(
+            limiter.retry_delay(&response, 100),
+            Some(MAX_THROTTLED_BACKOFF)
+        );
+
+        response.headers.insert(RETRY_AFTER, "3".parse().unwrap());
+        assert_eq!(
+            limiter.retry_delay(&response, 1),
+            Some(Duration::from_secs(3))
+        );
+
+        response
+            .headers
+            .insert(RETRY_AFTER, "86400".parse().unwrap());
+        assert_eq!(
+            limiter.retry_delay(&response, 1),
+            Some(MAX_THROTTLED_BACKOFF)
+        );
+
+        response.status = StatusCode::BAD_REQUEST;
+        assert_eq!(limiter.retry_delay(&response, 1), None);
+    }
+}
@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{Intasend, Transaction};
 
-use super::{Currency, Invoice, RequestClient, RequestMethods};
+use super::{de, Currency, Invoice, RequestClient, RequestMethods};
 
 /// `Refunds` struct implements methods for facilitating:
 /// listing all refunds made by an entity, creating new refunds for specific transactions with
@@ -78,6 +78,9 @@ impl RefundsAPI {
 
     /// The `create` method creates a new refund for a transaction whose status is **COMPLETE**.
     ///
+    /// `create` is not deduplicated: retrying it may refund the transaction twice. Use
+    /// `create_with_idempotency_key` for the refunds that may be retried.
+    ///
     /// ```rust
     /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
     /// dotenvy::dotenv().ok();
@@ -113,13 +116,37 @@ impl RefundsAPI {
         let service_path: &str = "/api/v1/chargebacks/";
         let request_method: RequestMethods = RequestMethods::Post;
 
-        let created_refund = &self
+        let created_refund = self
             .intasend
             .send::<RefundRequest, Refund>(Some(payload), service_path, request_method)
             .await?;
-        // println!("Json Response: {:#?}", created_refund);
 
-        Ok(created_refund.clone())
+        Ok(created_refund)
+    }
+
+    /// The `create_with_idempotency_key` method creates a new refund like `create`, under
+    /// `idempotency_key`: creating the refund again with the same key returns the original
+    /// refund (see the `idempotency` module).
+    ///
+    /// Two legitimate refunds of the same invoice, amount and reason need different keys.
+    pub async fn create_with_idempotency_key(
+        &self,
+        payload: RefundRequest,
+        idempotency_key: String,
+    ) -> Result<Refund, Error> {
+        let service_path: &str = "/api/v1/chargebacks/";
+        let request_method: RequestMethods = RequestMethods::Post;
+
+        self.intasend
+            .idempotent(format!("refund:{}", idempotency_key), || async {
+                let created_refund = self
+                    .intasend
+                    .send::<RefundRequest, Refund>(Some(payload), service_path, request_method)
+                    .await?;
+
+                Ok(created_refund)
+            })
+            .await
     }
 
     /// The `get` method returns the specific refund with the specified `chargeback_id`.
@@ -181,6 +208,7 @@ pub struct Refund {
     pub status: String,
     pub reason: RefundReason,
     pub resolution: Option<String>,
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub staff_created: bool,
     pub created_at: String,
     pub updated_at: String,
@@ -216,8 +244,11 @@ pub struct RefundRequest {
 /// `RefundListResponse` struct
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct RefundListResponse {
+    #[serde(default, deserialize_with = "de::lenient_number")]
     pub count: u32,
+    #[serde(default, deserialize_with = "de::lenient_string")]
     pub next: Option<String>,
+    #[serde(default, deserialize_with = "de::lenient_string")]
     pub previous: Option<String>,
     pub results: Vec<Refund>,
 }
@@ -0,0 +1,135 @@
+//! Pluggable async runtime support.
+//!
+//! The SDK itself only awaits futures, the few things it needs from an executor are
+//! described by the [`Runtime`] trait: timers, and driving the HTTP exchanges of the
+//! underlying `reqwest` client (whose connector needs a tokio reactor).
+//!
+//! Runtime adapters are enabled with cargo features:
+//! - `tokio` (default): [`TokioRuntime`]
+//! - `async-std`: [`AsyncStdRuntime`]
+//! - `smol`: [`SmolRuntime`]
+//!
+//! The `async-std` and `smol` adapters run the HTTP exchanges inside a compatibility
+//! layer, so the client works on those executors without a tokio runtime being set up.
+//! When no runtime feature is enabled, [`StdRuntime`] is used.
+//!
+//! ```rust
+//! # #[cfg(feature = "tokio")]
+//! # {
+//! let intasend = intasend::Intasend::new(
+//!     "publishable_key".to_string(),
+//!     "secret_key".to_string(),
+//!     true,
+//! )
+//! .with_runtime(intasend::runtime::TokioRuntime);
+//! # }
+//! ```
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+
+use super::{ApiResponse, IntasendClientError};
+
+/// A single HTTP exchange with the IntaSend API.
+pub type HttpExchange<'a> = BoxFuture<'a, Result<ApiResponse, IntasendClientError>>;
+
+/// `Runtime` trait - the executor facilities the `Intasend` client depends on
+pub trait Runtime: Debug + Send + Sync + 'static {
+    /// Returns a future that completes after `duration` has elapsed.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+
+    /// Drives a single HTTP exchange. Runtimes without a tokio reactor wrap the exchange
+    /// in a compatibility layer, the default implementation runs it as is.
+    fn http<'a>(&self, exchange: HttpExchange<'a>) -> HttpExchange<'a> {
+        exchange
+    }
+}
+
+/// `TokioRuntime` - runs the client on [tokio](https://tokio.rs)
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioRuntime;
+
+#[cfg(feature = "tokio")]
+impl Runtime for TokioRuntime {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        tokio::time::sleep(duration).boxed()
+    }
+}
+
+/// `AsyncStdRuntime` - runs the client on [async-std](https://async.rs)
+#[cfg(feature = "async-std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AsyncStdRuntime;
+
+#[cfg(feature = "async-std")]
+impl Runtime for AsyncStdRuntime {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        async_std::task::sleep(duration).boxed()
+    }
+
+    fn http<'a>(&self, exchange: HttpExchange<'a>) -> HttpExchange<'a> {
+        async_compat::Compat::new(exchange).boxed()
+    }
+}
+
+/// `SmolRuntime` - runs the client on [smol](https://github.com/smol-rs/smol)
+#[cfg(feature = "smol")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SmolRuntime;
+
+#[cfg(feature = "smol")]
+impl Runtime for SmolRuntime {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        async move {
+            smol::Timer::after(duration).await;
+        }
+        .boxed()
+    }
+
+    fn http<'a>(&self, exchange: HttpExchange<'a>) -> HttpExchange<'a> {
+        async_compat::Compat::new(exchange).boxed()
+    }
+}
+
+/// `StdRuntime` - executor independent timers backed by `std` threads.
+///
+/// This is the fallback when no runtime feature is enabled. The HTTP exchanges are run
+/// as is, so they still have to be polled from within a tokio context.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdRuntime;
+
+impl Runtime for StdRuntime {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        let (sender, receiver) = futures::channel::oneshot::channel::<()>();
+        std::thread::spawn(move || {
+            std::thread::sleep(duration);
+            let _ = sender.send(());
+        });
+
+        async move {
+            let _ = receiver.await;
+        }
+        .boxed()
+    }
+}
+
+/// Picks the runtime of the enabled features, `tokio` taking precedence over `async-std`
+/// which takes precedence over `smol`.
+pub(crate) fn default_runtime() -> Arc<dyn Runtime> {
+    #[cfg(feature = "tokio")]
+    return Arc::new(TokioRuntime);
+
+    #[cfg(all(not(feature = "tokio"), feature = "async-std"))]
+    return Arc::new(AsyncStdRuntime);
+
+    #[cfg(all(not(feature = "tokio"), not(feature = "async-std"), feature = "smol"))]
+    return Arc::new(SmolRuntime);
+
+    #[cfg(not(any(feature = "tokio", feature = "async-std", feature = "smol")))]
+    return Arc::new(StdRuntime);
+}
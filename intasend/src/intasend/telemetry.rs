@@ -0,0 +1,349 @@
+//! Observability helpers for the request pipeline of the `Intasend` client.
+//!
+//! With the `tracing` feature enabled every API call runs inside an `intasend_request`
+//! span carrying the endpoint, method, attempt number, response status, latency and the
+//! invoice/tracking ids involved. Request and response bodies are only ever logged with
+//! personal data (phone numbers, names, emails, accounts...) masked.
+//!
+//! With the `metrics` feature enabled the client records its measurements through the
+//! [`metrics`](https://docs.rs/metrics) facade, so they end up in whichever recorder
+//! (Prometheus, StatsD...) the application installed:
+//!
+//! | Metric                             | Kind      | Labels                                |
+//! | ---------------------------------- | --------- | ------------------------------------- |
+//! | `intasend_requests_total`          | counter   | `endpoint`, `method`                  |
+//! | `intasend_request_errors_total`    | counter   | `endpoint`, `method`, `status_class`  |
+//! | `intasend_request_duration_seconds`| histogram | `endpoint`, `method`                  |
+//! | `intasend_stk_push_total`          | counter   | `outcome` (`initiated`, `completed`, `failed`) |
+//! | `intasend_payouts_total`           | counter   | `provider`, `currency`                |
+//! | `intasend_payout_amount`           | histogram | `provider`, `currency`                |
+//!
+//! The `endpoint` label is the request path with the ids replaced by `{id}`, and the STK push
+//! outcomes are counted once per invoice, the first time a status check observes its final
+//! state.
+
+#[cfg(feature = "metrics")]
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+use serde_json::Value as JSON;
+
+use super::{ApiResponse, IntasendClientError};
+
+/// Fields that carry personal data and are masked before being logged
+const PII_FIELDS: [&str; 8] = [
+    "phone_number",
+    "account",
+    "id_number",
+    "first_name",
+    "last_name",
+    "name",
+    "email",
+    "address",
+];
+
+/// Masks a single value, keeping only the first and last two characters of long values.
+pub(crate) fn mask_str(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 4 {
+        return "*".repeat(chars.len());
+    }
+
+    let mut masked: String = chars[..2].iter().collect();
+    masked.push_str(&"*".repeat(chars.len() - 4));
+    masked.extend(&chars[chars.len() - 2..]);
+    masked
+}
+
+/// Returns a copy of the JSON `value` with all the personal data masked.
+pub(crate) fn mask(value: &JSON) -> JSON {
+    match value {
+        JSON::Object(map) => JSON::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        JSON::String(text) if PII_FIELDS.contains(&key.as_str()) => {
+                            JSON::String(mask_str(text))
+                        }
+                        other => mask(other),
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        JSON::Array(items) => JSON::Array(items.iter().map(mask).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Looks up a string id either at the top level of `value` or in its nested `invoice` object.
+pub(crate) fn find_id<'a>(value: &'a JSON, key: &str) -> Option<&'a str> {
+    value
+        .get(key)
+        .or_else(|| value.get("invoice").and_then(|invoice| invoice.get(key)))
+        .and_then(JSON::as_str)
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn request_span(method: &str, endpoint: &str, attempt: u32) -> tracing::Span {
+    tracing::info_span!(
+        "intasend_request",
+        method,
+        endpoint,
+        attempt,
+        status = tracing::field::Empty,
+        latency_ms = tracing::field::Empty,
+        invoice_id = tracing::field::Empty,
+        tracking_id = tracing::field::Empty,
+    )
+}
+
+/// Records the invoice and tracking ids found in a request or response body on `span`.
+#[cfg(feature = "tracing")]
+pub(crate) fn record_ids(span: &tracing::Span, body: &JSON) {
+    if let Some(invoice_id) = find_id(body, "invoice_id") {
+        span.record("invoice_id", invoice_id);
+    }
+    if let Some(tracking_id) = find_id(body, "tracking_id") {
+        span.record("tracking_id", tracking_id);
+    }
+}
+
+/// Records the ids of the outgoing request and logs its (masked) body.
+#[cfg(feature = "tracing")]
+pub(crate) fn trace_request(span: &tracing::Span, body: Option<&JSON>) {
+    if let Some(body) = body {
+        record_ids(span, body);
+        tracing::debug!(parent: span, body = %mask(body), "sending request");
+    }
+}
+
+/// Records the status and latency of a finished exchange and logs its outcome.
+#[cfg(feature = "tracing")]
+pub(crate) fn trace_outcome(
+    span: &tracing::Span,
+    outcome: &Result<ApiResponse, IntasendClientError>,
+    latency: Duration,
+) {
+    span.record("latency_ms", latency.as_millis() as u64);
+
+    match outcome {
+        Ok(response) => {
+            let status = response.status;
+            span.record("status", status.as_u16());
+            let body = response.json().unwrap_or(JSON::Null);
+            record_ids(span, &body);
+
+            if status.is_success() {
+                tracing::debug!(parent: span, "response received");
+            } else {
+                tracing::warn!(
+                    parent: span,
+                    status = status.as_u16(),
+                    body = %mask(&body),
+                    "IntaSend API returned an error"
+                );
+            }
+        }
+        Err(error) => tracing::error!(parent: span, %error, "request failed"),
+    }
+}
+
+/// Replaces the ids in `path` (wallet ids, uuids...) with `{id}` to keep label cardinality low.
+pub(crate) fn endpoint_label(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            let is_word = segment
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c == '-' || c == '_');
+            if is_word || segment == "v1" {
+                segment
+            } else {
+                "{id}"
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Records the count, latency and errors of a finished exchange.
+#[cfg(feature = "metrics")]
+pub(crate) fn record_request(
+    method: &str,
+    path: &str,
+    outcome: &Result<ApiResponse, IntasendClientError>,
+    latency: Duration,
+) {
+    let endpoint = endpoint_label(path);
+    let method = method.to_string();
+
+    metrics::counter!("intasend_requests_total", "endpoint" => endpoint.clone(), "method" => method.clone())
+        .increment(1);
+    metrics::histogram!(
+        "intasend_request_duration_seconds",
+        "endpoint" => endpoint.clone(),
+        "method" => method.clone()
+    )
+    .record(latency.as_secs_f64());
+
+    let status_class = match outcome {
+        Ok(response) if response.status.is_client_error() => "4xx",
+        Ok(response) if response.status.is_server_error() => "5xx",
+        Ok(_) => return,
+        Err(IntasendClientError::Middleware(_)) => "middleware",
+        Err(_) => "transport",
+    };
+    metrics::counter!(
+        "intasend_request_errors_total",
+        "endpoint" => endpoint,
+        "method" => method,
+        "status_class" => status_class
+    )
+    .increment(1);
+}
+
+/// Counts an STK push that was accepted by IntaSend.
+#[cfg(feature = "metrics")]
+pub(crate) fn record_stk_push_initiated() {
+    metrics::counter!("intasend_stk_push_total", "outcome" => "initiated").increment(1);
+}
+
+/// Number of invoices `StkPushOutcomes` remembers, the oldest ones are forgotten first
+#[cfg(feature = "metrics")]
+const STK_PUSH_OUTCOMES_CAPACITY: usize = 10_000;
+
+/// The STK push invoices whose final state was already counted, so that polling an invoice
+/// again does not count it twice.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default)]
+pub(crate) struct StkPushOutcomes {
+    counted: std::sync::Mutex<(HashSet<String>, VecDeque<String>)>,
+}
+
+#[cfg(feature = "metrics")]
+impl StkPushOutcomes {
+    /// Counts the final state of the invoice `invoice_id` the first time it is observed,
+    /// other states are ignored.
+    pub(crate) fn record(&self, invoice_id: &str, state: &str) {
+        let outcome = match state {
+            "COMPLETE" | "COMPLETED" => "completed",
+            "FAILED" => "failed",
+            _ => return,
+        };
+        if self.first_outcome(invoice_id) {
+            metrics::counter!("intasend_stk_push_total", "outcome" => outcome).increment(1);
+        }
+    }
+
+    /// Remembers `invoice_id`, returning whether it was not counted yet.
+    fn first_outcome(&self, invoice_id: &str) -> bool {
+        let mut counted = self
+            .counted
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        let (ids, order) = &mut *counted;
+        if !ids.insert(invoice_id.to_string()) {
+            return false;
+        }
+        order.push_back(invoice_id.to_string());
+        if order.len() > STK_PUSH_OUTCOMES_CAPACITY {
+            if let Some(oldest) = order.pop_front() {
+                ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+/// Counts an initiated payout batch and records the amount of each of its transactions.
+#[cfg(feature = "metrics")]
+pub(crate) fn record_payout(payload: &super::payouts::PayoutRequest) {
+    use rust_decimal::prelude::ToPrimitive;
+
+    let provider = label(&payload.provider);
+    let currency = label(&payload.currency);
+
+    metrics::counter!("intasend_payouts_total", "provider" => provider.clone(), "currency" => currency.clone())
+        .increment(1);
+    for transaction in &payload.transactions {
+        metrics::histogram!(
+            "intasend_payout_amount",
+            "provider" => provider.clone(),
+            "currency" => currency.clone()
+        )
+        .record(transaction.amount.to_f64().unwrap_or_default());
+    }
+}
+
+/// The serialized name of an enum variant, e.g. `MPESA-B2C` for `PayoutProvider::MpesaB2c`.
+#[cfg(feature = "metrics")]
+fn label<T: serde::Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(JSON::String(name)) => name,
+        _ => "unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn mask_hides_personal_data() {
+        let payload = json!({
+            "amount": "10.00",
+            "phone_number": "254712345678",
+            "api_ref": "order-1",
+            "transactions": [{ "name": "Joe Doe", "account": "254712345678" }],
+        });
+
+        let masked = mask(&payload);
+        assert_eq!(masked["phone_number"], "25********78");
+        assert_eq!(masked["transactions"][0]["name"], "Jo***oe");
+        assert_eq!(masked["transactions"][0]["account"], "25********78");
+        assert_eq!(masked["amount"], "10.00");
+        assert_eq!(masked["api_ref"], "order-1");
+    }
+
+    #[test]
+    fn find_id_looks_into_the_invoice() {
+        let response = json!({ "invoice": { "invoice_id": "RXX5P8R" } });
+        assert_eq!(find_id(&response, "invoice_id"), Some("RXX5P8R"));
+        assert_eq!(
+            find_id(&json!({ "tracking_id": "T1" }), "tracking_id"),
+            Some("T1")
+        );
+        assert_eq!(find_id(&response, "tracking_id"), None);
+    }
+
+    #[test]
+    fn endpoint_label_hides_ids() {
+        assert_eq!(
+            endpoint_label("/api/v1/wallets/Y7ERXJQ/transactions/"),
+            "/api/v1/wallets/{id}/transactions/"
+        );
+        assert_eq!(
+            endpoint_label("/api/v1/paymentlinks/e4f6126d-b374-4edb-bf17-f9240d24d66e"),
+            "/api/v1/paymentlinks/{id}"
+        );
+        assert_eq!(
+            endpoint_label("/api/v1/payment/mpesa-stk-push/"),
+            "/api/v1/payment/mpesa-stk-push/"
+        );
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn stk_push_outcomes_are_counted_once_per_invoice() {
+        let outcomes = StkPushOutcomes::default();
+        assert!(outcomes.first_outcome("RXX5P8R"));
+        assert!(!outcomes.first_outcome("RXX5P8R"));
+        assert!(outcomes.first_outcome("QYY6Q9S"));
+
+        for index in 0..STK_PUSH_OUTCOMES_CAPACITY {
+            outcomes.first_outcome(&index.to_string());
+        }
+        assert!(outcomes.first_outcome("RXX5P8R"));
+    }
+}
@@ -0,0 +1,347 @@
+//! Record-and-replay of the HTTP exchanges of the `Intasend` client.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use futures::FutureExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JSON;
+
+use super::super::runtime::HttpExchange;
+use super::super::telemetry::mask;
+use super::super::transport::Transport;
+use super::super::{ApiRequest, ApiResponse, IntasendClientError};
+
+/// The response headers kept in the cassettes
+const RECORDED_HEADERS: [HeaderName; 2] = [CONTENT_TYPE, RETRY_AFTER];
+
+/// A recorded request, without its headers
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub body: Option<JSON>,
+}
+
+/// A recorded response
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: JSON,
+}
+
+/// A recorded request/response pair
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+/// `Cassette` struct - a fixture file of recorded API exchanges
+///
+/// In record mode, the exchanges of the client go to the API and are appended to the
+/// file. In replay mode, no request leaves the process: the recorded response of the
+/// first unused interaction with the same method, path and body is returned.
+///
+/// Cassettes never contain credentials: the request headers are not recorded, and the
+/// keys of the client as well as personal data (phone numbers, names, emails,
+/// accounts...) are scrubbed from the bodies, the request bodies being scrubbed the same
+/// way before they are matched.
+///
+/// ```rust
+/// # fn run() -> std::io::Result<()> {
+/// use intasend::testing::Cassette;
+///
+/// // Records the exchanges against the sandbox on the first run, then replays them
+/// let cassette = Cassette::auto("tests/cassettes/wallets.json")?;
+///
+/// let intasend = intasend::Intasend::new(
+///     std::env::var("INTASEND_PUBLIC_KEY").unwrap_or_default(),
+///     std::env::var("INTASEND_SECRET_KEY").unwrap_or_default(),
+///     true,
+/// )
+/// .with_cassette(cassette);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    interactions: Arc<Mutex<Vec<(Interaction, bool)>>>,
+}
+
+impl Cassette {
+    /// Records the exchanges into the file at `path`, replacing its content
+    pub fn record<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            mode: Mode::Record,
+            interactions: Arc::default(),
+        }
+    }
+
+    /// Replays the exchanges recorded in the file at `path`
+    pub fn replay<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let interactions: Vec<Interaction> = serde_json::from_slice(&fs::read(&path)?)?;
+
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            mode: Mode::Replay,
+            interactions: Arc::new(Mutex::new(
+                interactions.into_iter().map(|i| (i, false)).collect(),
+            )),
+        })
+    }
+
+    /// Replays the file at `path` if it exists, records it otherwise
+    pub fn auto<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        if path.as_ref().exists() {
+            Self::replay(path)
+        } else {
+            Ok(Self::record(path))
+        }
+    }
+
+    /// Returns the recorded interactions
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.lock().iter().map(|(i, _)| i.clone()).collect()
+    }
+
+    /// Wraps `transport` to record or replay its exchanges, scrubbing `secrets`.
+    pub(crate) fn wrap(
+        self,
+        transport: Arc<dyn Transport>,
+        secrets: Vec<String>,
+    ) -> Arc<dyn Transport> {
+        let secrets = secrets.into_iter().filter(|s| !s.is_empty()).collect();
+        Arc::new(CassetteTransport {
+            cassette: self,
+            transport,
+            secrets,
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<(Interaction, bool)>> {
+        self.interactions
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn save(&self, interactions: &[(Interaction, bool)]) -> io::Result<()> {
+        let interactions: Vec<&Interaction> = interactions.iter().map(|(i, _)| i).collect();
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_vec_pretty(&interactions)?)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct CassetteTransport {
+    cassette: Cassette,
+    transport: Arc<dyn Transport>,
+    secrets: Vec<String>,
+}
+
+impl CassetteTransport {
+    /// Masks the personal data of `value` and replaces the secrets in its strings.
+    fn scrub(&self, value: &JSON) -> JSON {
+        self.scrub_secrets(mask(value))
+    }
+
+    fn scrub_secrets(&self, value: JSON) -> JSON {
+        match value {
+            JSON::String(text) => JSON::String(self.secrets.iter().fold(text, |text, secret| {
+                text.replace(secret.as_str(), "[scrubbed]")
+            })),
+            JSON::Array(items) => {
+                JSON::Array(items.into_iter().map(|i| self.scrub_secrets(i)).collect())
+            }
+            JSON::Object(map) => JSON::Object(
+                map.into_iter()
+                    .map(|(key, value)| (key, self.scrub_secrets(value)))
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+
+    fn recorded_request(&self, request: &ApiRequest) -> RecordedRequest {
+        RecordedRequest {
+            method: request.method.to_string(),
+            path: request.path().to_string(),
+            body: request.body.as_ref().map(|body| self.scrub(body)),
+        }
+    }
+
+    fn record(
+        &self,
+        request: RecordedRequest,
+        response: &ApiResponse,
+    ) -> Result<(), IntasendClientError> {
+        let body = match serde_json::from_slice::<JSON>(&response.body) {
+            Ok(body) => self.scrub(&body),
+            Err(_) => self.scrub(&JSON::String(
+                String::from_utf8_lossy(&response.body).into_owned(),
+            )),
+        };
+        let headers = RECORDED_HEADERS
+            .iter()
+            .filter_map(|name| {
+                let value = response.headers.get(name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect();
+
+        let interaction = Interaction {
+            request,
+            response: RecordedResponse {
+                status: response.status.as_u16(),
+                headers,
+                body,
+            },
+        };
+
+        let mut interactions = self.cassette.lock();
+        interactions.push((interaction, true));
+        self.cassette
+            .save(&interactions)
+            .map_err(|error| IntasendClientError::Transport(error.into()))
+    }
+
+    fn replay(&self, request: &RecordedRequest) -> Result<ApiResponse, IntasendClientError> {
+        let mut interactions = self.cassette.lock();
+
+        // Repeated requests (e.g. status polls) replay their recordings in order, the
+        // last one being reused once they are all used
+        let index = interactions
+            .iter()
+            .position(|(interaction, used)| !used && interaction.request == *request)
+            .or_else(|| {
+                interactions
+                    .iter()
+                    .rposition(|(interaction, _)| interaction.request == *request)
+            })
+            .ok_or_else(|| {
+                IntasendClientError::Transport(
+                    format!(
+                        "No recorded interaction for {} {} in {}",
+                        request.method,
+                        request.path,
+                        self.cassette.path.display()
+                    )
+                    .into(),
+                )
+            })?;
+        interactions[index].1 = true;
+
+        let recorded = &interactions[index].0.response;
+        let mut headers = HeaderMap::new();
+        for (name, value) in &recorded.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+        let body = match &recorded.body {
+            JSON::String(text) => text.clone().into_bytes(),
+            body => serde_json::to_vec(body)?,
+        };
+
+        Ok(ApiResponse {
+            status: StatusCode::from_u16(recorded.status)
+                .map_err(|error| IntasendClientError::Transport(error.into()))?,
+            headers,
+            body,
+        })
+    }
+}
+
+impl Transport for CassetteTransport {
+    fn send(&self, request: ApiRequest) -> HttpExchange<'static> {
+        let recorded = self.recorded_request(&request);
+
+        match self.cassette.mode {
+            Mode::Replay => futures::future::ready(self.replay(&recorded)).boxed(),
+            Mode::Record => {
+                let exchange = self.transport.send(request);
+                let recorder = self.clone();
+
+                async move {
+                    let response = exchange.await?;
+                    recorder.record(recorded, &response)?;
+                    Ok(response)
+                }
+                .boxed()
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "server", feature = "client"))]
+mod tests {
+    use super::super::MockServer;
+    use super::*;
+    use crate::Intasend;
+
+    fn cassette_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("intasend-{}-{}.json", name, std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn recorded_exchanges_replay_offline() {
+        let path = cassette_path("replay");
+        let server = MockServer::start();
+        let request = crate::MpesaStkPushRequest {
+            amount: rust_decimal::Decimal::new(100, 0),
+            phone_number: "254712345678".to_string(),
+            api_ref: Some("order-1".to_string()),
+            wallet_id: None,
+        };
+
+        let recording = server.client().with_cassette(Cassette::record(&path));
+        let recorded = recording
+            .collection()
+            .mpesa_stk_push(request.clone())
+            .await
+            .unwrap();
+        drop(server);
+
+        let cassette = std::fs::read_to_string(&path).unwrap();
+        assert!(!cassette.contains("254712345678"));
+        assert!(!cassette.contains(super::super::MOCK_SECRET_KEY));
+
+        let replaying = Intasend::new("pk".to_string(), "sk".to_string(), true)
+            .with_cassette(Cassette::replay(&path).unwrap());
+        let replayed = replaying
+            .collection()
+            .mpesa_stk_push(request)
+            .await
+            .unwrap();
+        assert_eq!(
+            replayed.invoice.unwrap().invoice_id,
+            recorded.invoice.unwrap().invoice_id
+        );
+
+        let error = replaying.wallets().list().await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<IntasendClientError>(),
+            Some(IntasendClientError::Transport(_))
+        ));
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,442 @@
+//! An in-process mock of the IntaSend API, for hermetic tests.
+//!
+//! [`MockServer`] serves the endpoints used by the SDK on a local port, backed by an
+//! in-memory [`MockState`]: STK push invoices progress through their states, payouts
+//! debit wallets and refunds reference transactions. Its [`client`](MockServer::client)
+//! is an `Intasend` client configured with the mock's keys and URL, so tests need neither
+//! credentials nor network.
+//!
+//! A [`Scenario`] scripts the failure paths deterministically: magic phone numbers whose
+//! STK pushes are cancelled, time out or fail for insufficient funds, payout accounts
+//! that fail, slower state transitions, and 5xx/429 faults injected per endpoint.
+//!
+//! A [`Cassette`] records the exchanges of a client with the real sandbox (secrets and
+//! personal data scrubbed) into a fixture file, and replays them offline.
+//!
+//! ```rust
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use intasend::testing::MockServer;
+//!
+//! let server = MockServer::start();
+//! let intasend = server.client();
+//!
+//! let wallets = intasend.wallets().list().await?;
+//! let balance = wallets.results[0].available_balance;
+//!
+//! let stkpush_request = intasend::MpesaStkPushRequest {
+//!     amount: rust_decimal::Decimal::new(100, 0),
+//!     phone_number: "254712345678".to_string(),
+//!     api_ref: None,
+//!     wallet_id: None,
+//! };
+//! let stkpush_response = intasend.collection().mpesa_stk_push(stkpush_request).await?;
+//!
+//! // The customer pays
+//! let invoice_id = stkpush_response.invoice.unwrap().invoice_id;
+//! server.state().complete_invoice(&invoice_id);
+//!
+//! let wallets = intasend.wallets().list().await?;
+//! assert_eq!(wallets.results[0].available_balance, balance + rust_decimal::Decimal::new(97, 0));
+//! # Ok(())
+//! # }
+//! ```
+
+mod cassette;
+mod scenario;
+mod state;
+
+use std::fmt;
+use std::io::Read;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+
+use serde_json::Value as JSON;
+
+use crate::Intasend;
+
+pub use self::cassette::{Cassette, Interaction, RecordedRequest, RecordedResponse};
+pub use self::scenario::{
+    Fault, Scenario, StkPushOutcome, PHONE_CANCELLED, PHONE_COMPLETES, PHONE_INSUFFICIENT_FUNDS,
+    PHONE_TIMEOUT,
+};
+pub use self::state::MockState;
+pub(crate) use self::state::{MockRequest, MockResponse};
+
+/// The publishable key accepted by a `MockServer`
+pub const MOCK_PUBLISHABLE_KEY: &str = "ISPubKey_test_mock";
+/// The secret key accepted by a `MockServer`
+pub const MOCK_SECRET_KEY: &str = "ISSecretKey_test_mock";
+
+/// `MockServer` struct - a local HTTP server mocking the IntaSend API
+///
+/// The server runs on a background thread until it is dropped.
+pub struct MockServer {
+    url: String,
+    state: Arc<Mutex<MockState>>,
+    server: Arc<tiny_http::Server>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Starts a mock server on a random local port, with the default `Scenario`
+    ///
+    /// Panics if the server cannot be bound.
+    pub fn start() -> Self {
+        Self::start_with(Scenario::default())
+    }
+
+    /// Starts a mock server on a random local port, following `scenario`
+    ///
+    /// Panics if the server cannot be bound.
+    pub fn start_with(scenario: Scenario) -> Self {
+        let server = tiny_http::Server::http("127.0.0.1:0")
+            .expect("[!] Failed to start the mock IntaSend server");
+        let server = Arc::new(server);
+        let url = format!("http://{}", server.server_addr());
+
+        let state = MockState::new(MOCK_PUBLISHABLE_KEY, MOCK_SECRET_KEY, &url, scenario);
+        let state = Arc::new(Mutex::new(state));
+
+        let thread = {
+            let (server, state) = (server.clone(), state.clone());
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    serve(&state, request);
+                }
+            })
+        };
+
+        Self {
+            url,
+            state,
+            server,
+            thread: Some(thread),
+        }
+    }
+
+    /// Returns the base URL of the server, e.g. `http://127.0.0.1:41234`
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns an `Intasend` client sending its requests to this server
+    pub fn client(&self) -> Intasend {
+        Intasend::new(
+            MOCK_PUBLISHABLE_KEY.to_string(),
+            MOCK_SECRET_KEY.to_string(),
+            true,
+        )
+        .with_base_url(&self.url)
+    }
+
+    /// Locks the state of the server, to inspect it or to simulate customer actions
+    pub fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl fmt::Debug for MockServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockServer")
+            .field("url", &self.url)
+            .finish()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn serve(state: &Mutex<MockState>, mut request: tiny_http::Request) {
+    let header = |name: &str| {
+        request
+            .headers()
+            .iter()
+            .find(|header| header.field.as_str().as_str().eq_ignore_ascii_case(name))
+            .map(|header| header.value.to_string())
+    };
+    let mut mock_request = MockRequest {
+        method: request.method().as_str().to_uppercase(),
+        path: request.url().to_string(),
+        authorization: header("Authorization"),
+        public_key: header("X-IntaSend-Public-API-Key"),
+        body: None,
+    };
+
+    let mut body = String::new();
+    if request.as_reader().read_to_string(&mut body).is_ok() && !body.trim().is_empty() {
+        mock_request.body = serde_json::from_str::<JSON>(&body).ok();
+    }
+    let response = state
+        .lock()
+        .unwrap_or_else(|error| error.into_inner())
+        .handle(&mock_request);
+
+    respond(request, response);
+}
+
+fn respond(request: tiny_http::Request, response: MockResponse) {
+    let content_type = tiny_http::Header::from_bytes("Content-Type", "application/json")
+        .expect("[!] Invalid content type header");
+    let body = match response.body {
+        JSON::Null => String::new(),
+        body => body.to_string(),
+    };
+    let mut http_response = tiny_http::Response::from_data(body)
+        .with_status_code(response.status)
+        .with_header(content_type);
+    for (name, value) in response.headers {
+        if let Ok(header) = tiny_http::Header::from_bytes(name, value) {
+            http_response.add_header(header);
+        }
+    }
+    let response = http_response;
+    let _ = request.respond(response);
+}
+
+#[cfg(all(test, feature = "server", feature = "client"))]
+mod tests {
+    use super::*;
+    use crate::{
+        MpesaStkPushRequest, PayoutApprovalRequest, PayoutRequest, PayoutRequestTransaction,
+        PayoutStatusRequest, RefundReason, RefundRequest, StkPushStatusRequest,
+    };
+    use rust_decimal_macros::dec;
+
+    fn stk_push(amount: rust_decimal::Decimal) -> MpesaStkPushRequest {
+        MpesaStkPushRequest {
+            amount,
+            phone_number: "254712345678".to_string(),
+            api_ref: None,
+            wallet_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn invoices_progress_and_credit_the_wallet() {
+        let server = MockServer::start();
+        let intasend = server.client();
+
+        let response = intasend
+            .collection()
+            .mpesa_stk_push(stk_push(dec!(1000)))
+            .await
+            .unwrap();
+        let invoice_id = response.invoice.unwrap().invoice_id;
+
+        let mut states = Vec::new();
+        for _ in 0..3 {
+            let status = intasend
+                .collection()
+                .status(StkPushStatusRequest {
+                    invoice_id: invoice_id.clone(),
+                    checkout_id: None,
+                    signature: None,
+                })
+                .await
+                .unwrap();
+            states.push(status.invoice.unwrap().state);
+        }
+        assert_eq!(states, ["PROCESSING", "COMPLETE", "COMPLETE"]);
+
+        let wallets = intasend.wallets().list().await.unwrap();
+        assert_eq!(wallets.results[0].available_balance, dec!(100970));
+
+        let refund = intasend
+            .refunds()
+            .create(RefundRequest {
+                invoice: invoice_id.clone(),
+                reason: RefundReason::Other,
+                reason_details: "test".to_string(),
+                amount: dec!(500),
+            })
+            .await
+            .unwrap();
+        assert_eq!(refund.transaction.invoice.unwrap().invoice_id, invoice_id);
+    }
+
+    #[tokio::test]
+    async fn approved_payouts_debit_the_wallet() {
+        let scenario = Scenario::new().failing_payout_account("254799999999", "Invalid account");
+        let server = MockServer::start_with(scenario);
+        let payouts = server.client().payouts();
+        let transaction = |account: &str, amount| PayoutRequestTransaction {
+            name: Some("Joe Doe".to_string()),
+            account: account.to_string(),
+            id_number: None,
+            amount,
+            bank_code: None,
+            category_name: None,
+            narrative: None,
+            account_type: None,
+            account_reference: None,
+        };
+
+        let payout = payouts
+            .mpesa_b2c(PayoutRequest {
+                currency: crate::Currency::Kes,
+                provider: None,
+                device_id: None,
+                callback_url: None,
+                batch_reference: None,
+                requires_approval: None,
+                transactions: vec![
+                    transaction("254712345678", dec!(2500)),
+                    transaction("254799999999", dec!(700)),
+                ],
+            })
+            .await
+            .unwrap();
+        assert!(matches!(payout.status_code.as_deref(), Some("BP103")));
+
+        let tracking_id = payout.tracking_id.clone().unwrap();
+        payouts
+            .approve(PayoutApprovalRequest {
+                tracking_id: tracking_id.clone(),
+                batch_reference: payout.batch_reference.clone().unwrap(),
+                nonce: payout.nonce.clone().unwrap(),
+                wallet: None,
+                transactions: None,
+            })
+            .await
+            .unwrap();
+        let payout = payouts
+            .status(PayoutStatusRequest { tracking_id })
+            .await
+            .unwrap();
+
+        assert_eq!(payout.status_code.as_deref(), Some("BC100"));
+        let codes: Vec<_> = payout
+            .transactions
+            .unwrap()
+            .into_iter()
+            .map(|transaction| transaction.status_code.unwrap())
+            .collect();
+        assert_eq!(codes, ["TS100", "TF103"]);
+        let balance = server.state().wallets()[0].available_balance;
+        assert_eq!(balance, dec!(100000) - dec!(2510));
+    }
+
+    #[tokio::test]
+    async fn payouts_are_approved_by_policy() {
+        use crate::payout_approval::{ApprovalDecision, PayoutApprovalPolicy, PendingPayout};
+        use std::time::SystemTime;
+
+        let server = MockServer::start();
+        let payouts = server.client().payouts();
+        let request = |amount, requires_approval| PayoutRequest {
+            currency: crate::Currency::Kes,
+            provider: Some(crate::PayoutProvider::MpesaB2c),
+            device_id: None,
+            callback_url: None,
+            batch_reference: None,
+            requires_approval,
+            transactions: vec![PayoutRequestTransaction {
+                name: None,
+                account: "254712345678".to_string(),
+                id_number: None,
+                amount,
+                bank_code: None,
+                category_name: None,
+                narrative: None,
+                account_type: None,
+                account_reference: None,
+            }],
+        };
+        let policy = PayoutApprovalPolicy::new()
+            .auto_approve_below(dec!(1000))
+            .required_approvals(2)
+            .known_beneficiaries(["254712345678"]);
+
+        let payout = payouts.initiate(request(dec!(5000), None)).await.unwrap();
+        let mut pending = PendingPayout::new(payout).made_by("alice");
+        pending.approve("bob", SystemTime::now()).unwrap();
+        let (record, approved) = payouts
+            .approve_with_policy(&pending, &policy, SystemTime::now())
+            .await
+            .unwrap();
+        assert!(matches!(
+            record.decision,
+            ApprovalDecision::AwaitApprovals { received: 1, .. }
+        ));
+        assert!(approved.is_none());
+        assert_eq!(server.state().wallets()[0].available_balance, dec!(100000));
+
+        pending.approve("carol", SystemTime::now()).unwrap();
+        let (record, approved) = payouts
+            .approve_with_policy(&pending, &policy, SystemTime::now())
+            .await
+            .unwrap();
+        assert!(record.is_approved());
+        assert_eq!(approved.unwrap().status_code.as_deref(), Some("BP104"));
+
+        // Without approval, the payout is processed on initiation
+        let payout = payouts
+            .initiate(request(dec!(100), Some(crate::PayoutApproval::No)))
+            .await
+            .unwrap();
+        assert_eq!(payout.status_code.as_deref(), Some("BP104"));
+    }
+
+    #[tokio::test]
+    async fn scenarios_script_failures_and_faults() {
+        let scenario = Scenario::new()
+            .transition_after(2)
+            .fault("/api/v1/payment/status/", Fault::ServerError(503), 1)
+            .fault("/api/v1/wallets/", Fault::Throttled { retry_after: 0 }, 1);
+        let server = MockServer::start_with(scenario);
+        let intasend = server
+            .client()
+            .with_rate_limiter(crate::rate_limit::RateLimiter::unlimited().retry_throttled(1));
+
+        let mut request = stk_push(dec!(100));
+        request.phone_number = PHONE_TIMEOUT.to_string();
+        let response = intasend.collection().mpesa_stk_push(request).await.unwrap();
+        let status = StkPushStatusRequest {
+            invoice_id: response.invoice.unwrap().invoice_id,
+            checkout_id: None,
+            signature: None,
+        };
+
+        let error = intasend
+            .collection()
+            .status(status.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<crate::IntasendClientError>(),
+            Some(crate::IntasendClientError::UnexpectedResponseStatus { status, .. })
+                if status.as_u16() == 503
+        ));
+
+        let mut states = Vec::new();
+        for _ in 0..4 {
+            let response = intasend.collection().status(status.clone()).await.unwrap();
+            states.push(response.invoice.unwrap().state);
+        }
+        assert_eq!(states, ["PENDING", "PROCESSING", "PROCESSING", "FAILED"]);
+
+        // The throttled request is retried by the rate limiter
+        assert!(intasend.wallets().list().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn requests_are_authenticated() {
+        let server = MockServer::start();
+        let intasend =
+            Intasend::new("pk".to_string(), "sk".to_string(), true).with_base_url(server.url());
+
+        let error = intasend.wallets().list().await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<crate::IntasendClientError>(),
+            Some(crate::IntasendClientError::UnexpectedResponseStatus { status, .. })
+                if status.as_u16() == 401
+        ));
+    }
+}
@@ -0,0 +1,165 @@
+//! Deterministic scenarios for the mock IntaSend server.
+
+/// STK pushes to this number complete
+pub const PHONE_COMPLETES: &str = "254700000001";
+/// STK pushes to this number are cancelled by the customer
+pub const PHONE_CANCELLED: &str = "254700000002";
+/// STK pushes to this number time out (the customer cannot be reached)
+pub const PHONE_TIMEOUT: &str = "254700000003";
+/// STK pushes to this number fail for insufficient funds
+pub const PHONE_INSUFFICIENT_FUNDS: &str = "254700000004";
+
+/// The outcome of an STK push invoice once it leaves the `PROCESSING` state
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StkPushOutcome {
+    /// The invoice completes and credits its wallet
+    Complete,
+    /// The invoice fails, the customer cancelled the request
+    Cancelled,
+    /// The invoice fails, the customer could not be reached
+    Timeout,
+    /// The invoice fails, the customer has insufficient funds
+    InsufficientFunds,
+}
+
+impl StkPushOutcome {
+    /// The `(failed_code, failed_reason)` of the failed invoices
+    pub(crate) fn failure(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Complete => None,
+            Self::Cancelled => Some(("1032", "Request cancelled by user")),
+            Self::Timeout => Some(("1037", "DS timeout user cannot be reached")),
+            Self::InsufficientFunds => {
+                Some(("1", "The balance is insufficient for the transaction"))
+            }
+        }
+    }
+}
+
+/// A fault injected in the responses of the mock server
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fault {
+    /// Responds with this 5xx status code
+    ServerError(u16),
+    /// Responds with a 429 status code and a `Retry-After` header of this many seconds
+    Throttled { retry_after: u64 },
+}
+
+#[derive(Clone, Debug)]
+struct FaultRule {
+    path_prefix: String,
+    fault: Fault,
+    remaining: u32,
+}
+
+/// `Scenario` struct - scripts the behaviour of a `MockServer`
+///
+/// By default, STK pushes complete except for the magic phone numbers (`PHONE_*`), state
+/// transitions happen on every status query, and no payout or request fails.
+///
+/// ```rust
+/// use intasend::testing::{Fault, MockServer, Scenario, StkPushOutcome};
+///
+/// let scenario = Scenario::new()
+///     .stk_push("254712345678", StkPushOutcome::Timeout)
+///     .failing_payout_account("254799999999", "Invalid account")
+///     .transition_after(3)
+///     .fault("/api/v1/payment/status/", Fault::ServerError(503), 2)
+///     .fault("/api/v1/send-money/", Fault::Throttled { retry_after: 1 }, 1);
+///
+/// let server = MockServer::start_with(scenario);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    stk_pushes: Vec<(String, StkPushOutcome)>,
+    failing_accounts: Vec<(String, String)>,
+    transition_polls: u32,
+    faults: Vec<FaultRule>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            stk_pushes: vec![
+                (PHONE_COMPLETES.to_string(), StkPushOutcome::Complete),
+                (PHONE_CANCELLED.to_string(), StkPushOutcome::Cancelled),
+                (PHONE_TIMEOUT.to_string(), StkPushOutcome::Timeout),
+                (
+                    PHONE_INSUFFICIENT_FUNDS.to_string(),
+                    StkPushOutcome::InsufficientFunds,
+                ),
+            ],
+            failing_accounts: Vec::new(),
+            transition_polls: 1,
+            faults: Vec::new(),
+        }
+    }
+}
+
+impl Scenario {
+    /// Creates the default scenario
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the outcome of the STK pushes to `phone_number`
+    pub fn stk_push(mut self, phone_number: &str, outcome: StkPushOutcome) -> Self {
+        self.stk_pushes
+            .insert(0, (phone_number.to_string(), outcome));
+        self
+    }
+
+    /// Fails the payout transactions to `account` with `reason`, their amount and fee are
+    /// credited back to the wallet
+    pub fn failing_payout_account(mut self, account: &str, reason: &str) -> Self {
+        self.failing_accounts
+            .push((account.to_string(), reason.to_string()));
+        self
+    }
+
+    /// Makes the invoices and payouts change state only every `polls` status queries
+    pub fn transition_after(mut self, polls: u32) -> Self {
+        self.transition_polls = polls.max(1);
+        self
+    }
+
+    /// Responds to the next `times` requests whose path starts with `path_prefix` with
+    /// `fault`, instead of handling them
+    pub fn fault(mut self, path_prefix: &str, fault: Fault, times: u32) -> Self {
+        self.faults.push(FaultRule {
+            path_prefix: path_prefix.to_string(),
+            fault,
+            remaining: times,
+        });
+        self
+    }
+
+    pub(crate) fn stk_push_outcome(&self, phone_number: &str) -> StkPushOutcome {
+        self.stk_pushes
+            .iter()
+            .find(|(phone, _)| phone == phone_number)
+            .map(|(_, outcome)| *outcome)
+            .unwrap_or(StkPushOutcome::Complete)
+    }
+
+    pub(crate) fn payout_failure(&self, account: &str) -> Option<&str> {
+        self.failing_accounts
+            .iter()
+            .find(|(failing, _)| failing == account)
+            .map(|(_, reason)| reason.as_str())
+    }
+
+    pub(crate) fn transition_polls(&self) -> u32 {
+        self.transition_polls
+    }
+
+    /// Takes the fault to inject for a request to `path`, if any.
+    pub(crate) fn take_fault(&mut self, path: &str) -> Option<Fault> {
+        let rule = self
+            .faults
+            .iter_mut()
+            .find(|rule| rule.remaining > 0 && path.starts_with(rule.path_prefix.as_str()))?;
+        rule.remaining -= 1;
+        Some(rule.fault)
+    }
+}
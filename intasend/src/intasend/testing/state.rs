@@ -0,0 +1,1131 @@
+//! In-memory state and request handling of the mock IntaSend server.
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value as JSON};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use super::super::checkout::{
+    CheckoutDefaults, CheckoutDetailsResponse, CheckoutMethod, CheckoutSignature,
+};
+use super::super::collection::{Meta, MpesaStkPushResponse, StkPushStatusRequest};
+use super::super::payment_links::{PaymentLink, PaymentLinksCreateDetails};
+use super::super::payouts::{
+    Payout, PayoutApproval, PayoutApprovalRequest, PayoutCancelRequest, PayoutRequest,
+    PayoutResponseTransaction, PayoutStatusRequest,
+};
+use super::super::refunds::{Refund, RefundRequest};
+use super::super::wallets::{Wallet, WalletCreateDetails, WalletIntraTransferRequest, WalletType};
+use super::super::{
+    CardInfo, Currency, Customer, Invoice, Tarrif, Transaction, TransactionStatus, TransactionType,
+};
+use super::scenario::{Fault, Scenario, StkPushOutcome};
+
+/// The flat fee charged by the mock for every payout transaction
+const PAYOUT_FEE: Decimal = dec!(10);
+/// The share of the collected amounts charged by the mock
+const COLLECTION_RATE: Decimal = dec!(0.03);
+/// The lifetime of the checkout signatures, in seconds
+const SIGNATURE_LIFETIME: u64 = 3600;
+/// The IntaSend account id of the mock
+const MOCK_ACCOUNT_ID: &str = "MOCK001";
+
+/// A request received by the mock server
+#[derive(Clone, Debug)]
+pub(crate) struct MockRequest {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    pub(crate) authorization: Option<String>,
+    pub(crate) public_key: Option<String>,
+    pub(crate) body: Option<JSON>,
+}
+
+/// The response of the mock server to a `MockRequest`
+#[derive(Clone, Debug)]
+pub(crate) struct MockResponse {
+    pub(crate) status: u16,
+    pub(crate) headers: Vec<(&'static str, String)>,
+    pub(crate) body: JSON,
+}
+
+impl MockResponse {
+    fn ok(body: JSON) -> Self {
+        Self {
+            status: 200,
+            headers: Vec::new(),
+            body,
+        }
+    }
+
+    fn no_content() -> Self {
+        Self {
+            status: 204,
+            ..Self::ok(JSON::Null)
+        }
+    }
+
+    fn created(body: JSON) -> Self {
+        Self {
+            status: 201,
+            ..Self::ok(body)
+        }
+    }
+
+    /// An error in the format of the IntaSend API (`IntasendApiError`)
+    pub(crate) fn error(status: u16, code: &str, detail: &str) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: json!({
+                "type": if status >= 500 { "server_error" } else { "client_error" },
+                "errors": [{ "code": code, "detail": detail, "attr": null }],
+            }),
+        }
+    }
+
+    fn not_found(detail: &str) -> Self {
+        Self::error(404, "not_found", detail)
+    }
+
+    fn invalid(detail: &str) -> Self {
+        Self::error(400, "invalid", detail)
+    }
+}
+
+type Handled = Result<MockResponse, MockResponse>;
+
+#[derive(Clone, Debug)]
+struct MockInvoice {
+    invoice: Invoice,
+    customer: Customer,
+    wallet_id: String,
+    meta_id: String,
+    outcome: StkPushOutcome,
+    polls: u32,
+}
+
+#[derive(Clone, Debug)]
+struct MockPayout {
+    payout: Payout,
+    wallet_id: String,
+    approved: bool,
+    polls: u32,
+}
+
+/// `MockState` struct - the in-memory state of a `MockServer`
+///
+/// Wallets start with a funded KES settlement wallet. STK push invoices move from
+/// `PENDING` to `PROCESSING` to `COMPLETE` (or `FAILED`, see `Scenario`) as their status
+/// is queried, completed invoices credit their wallet. Approved payouts debit the
+/// settlement wallet of their currency and complete on a later status query. Refunds
+/// reference the sale transaction of a completed invoice.
+#[derive(Debug)]
+pub struct MockState {
+    publishable_key: String,
+    secret_key: String,
+    host: String,
+    scenario: Scenario,
+    counter: u64,
+    clock: u64,
+    wallets: Vec<Wallet>,
+    transactions: Vec<(String, Transaction)>,
+    invoices: Vec<MockInvoice>,
+    checkouts: Vec<CheckoutDetailsResponse>,
+    payouts: Vec<MockPayout>,
+    refunds: Vec<Refund>,
+    payment_links: Vec<PaymentLink>,
+}
+
+impl MockState {
+    pub(crate) fn new(
+        publishable_key: &str,
+        secret_key: &str,
+        host: &str,
+        scenario: Scenario,
+    ) -> Self {
+        let mut state = Self {
+            publishable_key: publishable_key.to_string(),
+            secret_key: secret_key.to_string(),
+            host: host.to_string(),
+            scenario,
+            counter: 0,
+            clock: 0,
+            wallets: Vec::new(),
+            transactions: Vec::new(),
+            invoices: Vec::new(),
+            checkouts: Vec::new(),
+            payouts: Vec::new(),
+            refunds: Vec::new(),
+            payment_links: Vec::new(),
+        };
+
+        let settlement = state.add_wallet(Currency::Kes, WalletType::Settlement, "default", true);
+        state.fund_wallet(&settlement, dec!(100000));
+        state
+    }
+
+    /// Replaces the scenario of the server, for the requests to come
+    pub fn set_scenario(&mut self, scenario: Scenario) {
+        self.scenario = scenario;
+    }
+
+    /// Returns the wallets, the settlement wallets first
+    pub fn wallets(&self) -> &[Wallet] {
+        &self.wallets
+    }
+
+    /// Returns the wallet with `wallet_id`
+    pub fn wallet(&self, wallet_id: &str) -> Option<&Wallet> {
+        self.wallets
+            .iter()
+            .find(|wallet| wallet.wallet_id == wallet_id)
+    }
+
+    /// Returns the invoice with `invoice_id`
+    pub fn invoice(&self, invoice_id: &str) -> Option<&Invoice> {
+        self.invoices
+            .iter()
+            .find(|invoice| invoice.invoice.invoice_id == invoice_id)
+            .map(|invoice| &invoice.invoice)
+    }
+
+    /// Returns the refunds, in creation order
+    pub fn refunds(&self) -> &[Refund] {
+        &self.refunds
+    }
+
+    /// Deposits `amount` in the wallet with `wallet_id`
+    pub fn fund_wallet(&mut self, wallet_id: &str, amount: Decimal) {
+        self.post_transaction(wallet_id, amount, TransactionType::Deposit, "Deposit", None);
+    }
+
+    /// Completes the invoice with `invoice_id` (as if the customer paid), crediting its wallet
+    pub fn complete_invoice(&mut self, invoice_id: &str) {
+        let Some(index) = self.invoice_index(invoice_id) else {
+            return;
+        };
+        if self.invoices[index].invoice.state == "COMPLETE" {
+            return;
+        }
+
+        let now = self.now();
+        let invoice = &mut self.invoices[index];
+        invoice.invoice.state = "COMPLETE".to_string();
+        invoice.invoice.mpesa_reference = Some(format!("MOCK{}", &invoice_id[1..]));
+        invoice.invoice.updated_at = now;
+
+        let (wallet_id, invoice) = (invoice.wallet_id.clone(), invoice.invoice.clone());
+        let narrative = format!("Payment {}", invoice.invoice_id);
+        self.post_transaction(
+            &wallet_id,
+            invoice.net_amount,
+            TransactionType::Sale,
+            &narrative,
+            Some(invoice),
+        );
+    }
+
+    /// Fails the invoice with `invoice_id` (as if the customer cancelled the STK push)
+    pub fn fail_invoice(&mut self, invoice_id: &str, reason: &str) {
+        self.fail_invoice_with(invoice_id, "1032", reason);
+    }
+
+    fn fail_invoice_with(&mut self, invoice_id: &str, code: &str, reason: &str) {
+        let now = self.now();
+        if let Some(invoice) = self
+            .invoices
+            .iter_mut()
+            .find(|invoice| invoice.invoice.invoice_id == invoice_id)
+        {
+            invoice.invoice.state = "FAILED".to_string();
+            invoice.invoice.failed_reason = Some(reason.to_string());
+            invoice.invoice.failed_code = Some(code.to_string());
+            invoice.invoice.updated_at = now;
+        }
+    }
+
+    /// Marks the checkout with `checkout_id` as paid, crediting its wallet (by default the
+    /// settlement wallet of its currency)
+    /// Sets the payment method picked by the customer of a checkout
+    pub fn choose_checkout_method(&mut self, checkout_id: &str, method: CheckoutMethod) {
+        if let Some(checkout) = self
+            .checkouts
+            .iter_mut()
+            .find(|checkout| checkout.id == checkout_id)
+        {
+            checkout.method = Some(method);
+        }
+    }
+
+    pub fn pay_checkout(&mut self, checkout_id: &str) {
+        let Some(checkout) = self
+            .checkouts
+            .iter_mut()
+            .find(|checkout| checkout.id == checkout_id && !checkout.paid)
+        else {
+            return;
+        };
+        checkout.paid = true;
+
+        let (amount, currency) = (checkout.amount, checkout.currency.clone());
+        let wallet_id = checkout.wallet_id.clone();
+        let wallet_id =
+            wallet_id.or_else(|| self.settlement_wallet(&currency.unwrap_or(Currency::Kes)));
+        if let Some(wallet_id) = wallet_id {
+            let narrative = format!("Checkout {}", checkout_id);
+            self.post_transaction(&wallet_id, amount, TransactionType::Sale, &narrative, None);
+        }
+    }
+
+    /// Handles a request to the IntaSend API
+    pub(crate) fn handle(&mut self, request: &MockRequest) -> MockResponse {
+        match self.scenario.take_fault(&request.path) {
+            Some(Fault::ServerError(status)) => {
+                MockResponse::error(status, "error", "A server error occurred.")
+            }
+            Some(Fault::Throttled { retry_after }) => MockResponse {
+                headers: vec![("Retry-After", retry_after.to_string())],
+                ..MockResponse::error(429, "throttled", "Request was throttled.")
+            },
+            None => self.route(request).unwrap_or_else(|response| response),
+        }
+    }
+
+    fn route(&mut self, request: &MockRequest) -> Handled {
+        let path = request.path.split('?').next().unwrap_or_default();
+        let path = path.trim_start_matches("/api/v1/").trim_end_matches('/');
+        let segments: Vec<&str> = path.split('/').collect();
+        let method = request.method.as_str();
+
+        match (method, segments.as_slice()) {
+            ("POST", ["checkout"]) => {
+                self.authorize_public(request)?;
+                self.checkout(body(request)?)
+            }
+            ("POST", ["checkout", "details"]) => {
+                self.authorize_public(request)?;
+                self.checkout_details(body(request)?)
+            }
+            ("GET", ["send-money", "bank-codes", "ke"]) => {
+                self.authorize_public(request)?;
+                Ok(MockResponse::ok(json!([
+                    { "bank_name": "Equity Bank", "bank_code": "68" },
+                    { "bank_name": "KCB Bank", "bank_code": "01" },
+                    { "bank_name": "Co-operative Bank", "bank_code": "11" },
+                ])))
+            }
+            _ => {
+                self.authorize_secret(request)?;
+                self.route_secret(method, &segments, request)
+            }
+        }
+    }
+
+    fn route_secret(&mut self, method: &str, segments: &[&str], request: &MockRequest) -> Handled {
+        match (method, segments) {
+            ("POST", ["payment", "mpesa-stk-push"]) => self.stk_push(body(request)?),
+            ("POST", ["payment", "status"]) => self.stk_push_status(body(request)?),
+            ("POST", ["send-money", "initiate"]) => self.payout_initiate(body(request)?),
+            ("POST", ["send-money", "approve"]) => self.payout_approve(body(request)?),
+            ("POST", ["send-money", "status"]) => self.payout_status(body(request)?),
+            ("POST", ["send-money", "cancel"]) => self.payout_cancel(body(request)?),
+            ("GET", ["wallets"]) => Ok(MockResponse::ok(page(&self.wallets))),
+            ("POST", ["wallets"]) => self.wallet_create(body(request)?),
+            ("GET", ["wallets", wallet_id]) => self
+                .wallet(wallet_id)
+                .map(|wallet| MockResponse::ok(json!(wallet)))
+                .ok_or_else(|| MockResponse::not_found("Wallet not found.")),
+            ("GET", ["wallets", wallet_id, "transactions"]) => {
+                self.wallet(wallet_id)
+                    .ok_or_else(|| MockResponse::not_found("Wallet not found."))?;
+                let transactions: Vec<&Transaction> = self
+                    .transactions
+                    .iter()
+                    .rev()
+                    .filter(|(id, _)| id == wallet_id)
+                    .map(|(_, transaction)| transaction)
+                    .collect();
+                Ok(MockResponse::ok(page(&transactions)))
+            }
+            ("POST", ["wallets", wallet_id, "intra_transfer"]) => {
+                self.intra_transfer(wallet_id, body(request)?)
+            }
+            ("GET", ["chargebacks"]) => Ok(MockResponse::ok(page(&self.refunds))),
+            ("POST", ["chargebacks"]) => self.refund_create(body(request)?),
+            ("GET", ["chargebacks", chargeback_id]) => self
+                .refunds
+                .iter()
+                .find(|refund| refund.chargeback_id == *chargeback_id)
+                .map(|refund| MockResponse::ok(json!(refund)))
+                .ok_or_else(|| MockResponse::not_found("Chargeback not found.")),
+            ("GET", ["paymentlinks"]) => Ok(MockResponse::ok(page(&self.payment_links))),
+            ("POST", ["paymentlinks"]) => self.payment_link_create(body(request)?),
+            ("GET", ["paymentlinks", link_id]) => self
+                .payment_link(link_id)
+                .map(|link| MockResponse::ok(json!(link)))
+                .ok_or_else(|| MockResponse::not_found("Payment link not found.")),
+            ("PUT" | "PATCH", ["paymentlinks", link_id]) => {
+                self.payment_link_update(link_id, request.body.clone())
+            }
+            ("DELETE", ["paymentlinks", link_id]) => {
+                let count = self.payment_links.len();
+                self.payment_links
+                    .retain(|link| link.id.to_string() != *link_id);
+                if self.payment_links.len() == count {
+                    return Err(MockResponse::not_found("Payment link not found."));
+                }
+                Ok(MockResponse::no_content())
+            }
+            _ => Err(MockResponse::not_found("Not found.")),
+        }
+    }
+
+    fn authorize_public(&self, request: &MockRequest) -> Result<(), MockResponse> {
+        match &request.public_key {
+            Some(key) if *key == self.publishable_key => Ok(()),
+            _ => Err(unauthenticated()),
+        }
+    }
+
+    fn authorize_secret(&self, request: &MockRequest) -> Result<(), MockResponse> {
+        match &request.authorization {
+            Some(value) if *value == format!("Bearer {}", self.secret_key) => Ok(()),
+            _ => Err(unauthenticated()),
+        }
+    }
+
+    fn stk_push(&mut self, payload: StkPushPayload) -> Handled {
+        if payload.amount <= Decimal::ZERO {
+            return Err(MockResponse::invalid(
+                "Ensure this value is greater than 0.",
+            ));
+        }
+        let phone_number = payload.phone_number.trim_start_matches('+');
+        if !(phone_number.len() == 12
+            && phone_number.starts_with("254")
+            && phone_number.chars().all(|c| c.is_ascii_digit()))
+        {
+            return Err(MockResponse::invalid("Invalid phone number."));
+        }
+
+        let currency = payload.currency.unwrap_or(Currency::Kes);
+        let wallet_id = match payload.wallet_id {
+            Some(wallet_id) => self
+                .wallet(&wallet_id)
+                .map(|wallet| wallet.wallet_id.clone())
+                .ok_or_else(|| MockResponse::invalid("Wallet not found."))?,
+            None => self
+                .settlement_wallet(&currency)
+                .ok_or_else(|| MockResponse::invalid("No settlement wallet for currency."))?,
+        };
+
+        let now = self.now();
+        let charges = (payload.amount * COLLECTION_RATE).round_dp(2);
+        let invoice = Invoice {
+            invoice_id: self.next_id("I"),
+            state: "PENDING".to_string(),
+            provider: "M-PESA".to_string(),
+            charges,
+            net_amount: payload.amount - charges,
+            currency: currency_code(&currency).to_string(),
+            value: payload.amount,
+            account: phone_number.to_string(),
+            api_ref: payload.api_ref,
+            mpesa_reference: None,
+            host: self.host.clone(),
+            card_info: CardInfo {
+                bin_country: None,
+                card_type: None,
+            },
+            retry_count: 0,
+            failed_reason: None,
+            failed_code: None,
+            failed_code_link: None,
+            created_at: now.clone(),
+            updated_at: now.clone(),
+        };
+        let customer = Customer {
+            customer_id: self.next_id("U"),
+            phone_number: phone_number.to_string(),
+            email: None,
+            first_name: None,
+            last_name: None,
+            country: Some("KE".to_string()),
+            zipcode: None,
+            provider: "M-PESA".to_string(),
+            created_at: now.clone(),
+            updated_at: now.clone(),
+        };
+
+        let response = MpesaStkPushResponse {
+            invoice: Some(invoice.clone()),
+            customer: Some(customer.clone()),
+            payment_link: None,
+            refundable: false,
+            created_at: now.clone(),
+            updated_at: now,
+        };
+        self.invoices.push(MockInvoice {
+            invoice,
+            customer,
+            wallet_id,
+            meta_id: Uuid::new_v4().to_string(),
+            outcome: self.scenario.stk_push_outcome(phone_number),
+            polls: 0,
+        });
+
+        Ok(MockResponse::created(json!(response)))
+    }
+
+    fn stk_push_status(&mut self, payload: StkPushStatusRequest) -> Handled {
+        let index = self
+            .invoice_index(&payload.invoice_id)
+            .ok_or_else(|| MockResponse::not_found("Invoice not found."))?;
+
+        let invoice = &mut self.invoices[index];
+        invoice.polls += 1;
+        if invoice.polls >= self.scenario.transition_polls() {
+            invoice.polls = 0;
+            match (invoice.invoice.state.as_str(), invoice.outcome.failure()) {
+                ("PENDING", _) => {
+                    let now = self.now();
+                    let invoice = &mut self.invoices[index].invoice;
+                    invoice.state = "PROCESSING".to_string();
+                    invoice.updated_at = now;
+                }
+                ("PROCESSING", None) => self.complete_invoice(&payload.invoice_id),
+                ("PROCESSING", Some((code, reason))) => {
+                    self.fail_invoice_with(&payload.invoice_id, code, reason)
+                }
+                _ => {}
+            }
+        }
+
+        let invoice = &self.invoices[index];
+        let meta = Meta {
+            id: invoice.meta_id.clone(),
+            customer_comment: None,
+            payment_link: None,
+            customer: Some(invoice.customer.clone()),
+            created_at: invoice.invoice.created_at.clone(),
+            updated_at: invoice.invoice.updated_at.clone(),
+        };
+        Ok(MockResponse::ok(json!({
+            "invoice": invoice.invoice,
+            "meta": meta,
+        })))
+    }
+
+    fn checkout(&mut self, payload: CheckoutPayload) -> Handled {
+        if payload.amount <= Decimal::ZERO {
+            return Err(MockResponse::invalid(
+                "Ensure this value is greater than 0.",
+            ));
+        }
+        if let Some(wallet_id) = &payload.wallet_id {
+            self.wallet(wallet_id)
+                .ok_or_else(|| MockResponse::invalid("Wallet not found."))?;
+        }
+
+        let now = self.now();
+        let id = Uuid::new_v4().to_string();
+        // Signatures expire in real time, for the clients to check them
+        let issued_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let checkout = CheckoutDetailsResponse {
+            url: format!("{}/checkout/{}/express/", self.host, id),
+            signature: CheckoutSignature {
+                scope: Some("express-checkout".to_string()),
+                issuer: Some("IntaSend Solutions Limited".to_string()),
+                audience: vec![id.clone()],
+                issued_at,
+                expires_at: issued_at + SIGNATURE_LIFETIME,
+                account_id: Some(MOCK_ACCOUNT_ID.to_string()),
+                reference: Some(id.clone()),
+            }
+            .encode(),
+            id,
+            first_name: payload.first_name,
+            last_name: payload.last_name,
+            phone_number: payload.phone_number,
+            email: payload.email,
+            country: payload.country,
+            address: payload.address,
+            city: payload.city,
+            state: payload.state,
+            zipcode: payload.zipcode,
+            api_ref: payload.api_ref,
+            wallet_id: payload.wallet_id,
+            method: payload.method,
+            channel: Some("API".to_string()),
+            host: payload.host.or_else(|| Some(self.host.clone())),
+            is_mobile: false,
+            version: None,
+            redirect_url: payload.redirect_url,
+            amount: payload.amount,
+            currency: Some(payload.currency.clone()),
+            paid: false,
+            mobile_tarrif: payload.mobile_tarrif.unwrap_or(Tarrif::BusinessPays),
+            card_tarrif: payload.card_tarrif.unwrap_or(Tarrif::BusinessPays),
+            bitcoin_tarrif: payload.bitcoin_tarrif.unwrap_or(Tarrif::BusinessPays),
+            ach_tarrif: payload.ach_tarrif.unwrap_or(Tarrif::BusinessPays),
+            created_at: now.clone(),
+            updated_at: now,
+            defaults: CheckoutDefaults {
+                enable_card_payment: true,
+                enable_mpesa_payment: true,
+                enable_bitcoin_payment: false,
+                enable_ach_payment: false,
+                default_currency: payload.currency,
+                default_tarrif: Tarrif::BusinessPays,
+            },
+        };
+
+        let response = json!(checkout);
+        self.checkouts.push(checkout);
+        Ok(MockResponse::created(response))
+    }
+
+    fn checkout_details(&mut self, payload: CheckoutDetailsPayload) -> Handled {
+        self.checkouts
+            .iter()
+            .find(|checkout| checkout.id == payload.checkout_id)
+            .filter(|checkout| checkout.signature == payload.signature)
+            .map(|checkout| MockResponse::ok(json!(checkout)))
+            .ok_or_else(|| MockResponse::not_found("Checkout not found."))
+    }
+
+    fn payout_initiate(&mut self, payload: PayoutRequest) -> Handled {
+        if payload.provider.is_none() {
+            return Err(MockResponse::invalid("Provider is required."));
+        }
+        if payload.transactions.is_empty() {
+            return Err(MockResponse::invalid("Transactions are required."));
+        }
+        if payload
+            .transactions
+            .iter()
+            .any(|transaction| transaction.amount <= Decimal::ZERO)
+        {
+            return Err(MockResponse::invalid(
+                "Ensure this value is greater than 0.",
+            ));
+        }
+        let wallet_id = self
+            .settlement_wallet(&payload.currency)
+            .ok_or_else(|| MockResponse::invalid("No settlement wallet for currency."))?;
+        let requires_approval = payload.requires_approval != Some(PayoutApproval::No);
+
+        let amount: Decimal = payload.transactions.iter().map(|t| t.amount).sum();
+        let charges = PAYOUT_FEE * Decimal::from(payload.transactions.len());
+        let transactions = payload
+            .transactions
+            .iter()
+            .map(|transaction| PayoutResponseTransaction {
+                status: Some("Pending".to_string()),
+                status_code: Some("TP101".to_string()),
+                request_reference_id: Some(Uuid::new_v4().to_string()),
+                name: transaction.name.clone(),
+                account: transaction.account.clone(),
+                id_number: transaction.id_number.clone(),
+                bank_code: transaction.bank_code.clone(),
+                amount: transaction.amount,
+                narrative: transaction.narrative.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let payout = Payout {
+            file_id: Some(self.next_id("F")),
+            device_id: payload.device_id,
+            tracking_id: Some(Uuid::new_v4().to_string()),
+            batch_reference: payload.batch_reference,
+            status: Some("Preview and approve".to_string()),
+            status_code: Some("BP103".to_string()),
+            nonce: Some(self.next_id("N")),
+            wallet: self.wallet(&wallet_id).cloned(),
+            transactions_count: Some(transactions.len() as u32),
+            transactions: Some(transactions),
+            charge_estimate: Some(charges),
+            total_amount_estimate: Some(amount + charges),
+            total_amount: Some(amount),
+        };
+
+        let response = json!(payout);
+        let approval = PayoutApprovalRequest {
+            tracking_id: payout.tracking_id.clone().unwrap_or_default(),
+            batch_reference: payout.batch_reference.clone().unwrap_or_default(),
+            nonce: payout.nonce.clone().unwrap_or_default(),
+            wallet: None,
+            transactions: None,
+        };
+        self.payouts.push(MockPayout {
+            payout,
+            wallet_id,
+            approved: false,
+            polls: 0,
+        });
+        if !requires_approval {
+            return self.payout_approve(approval);
+        }
+        Ok(MockResponse::ok(response))
+    }
+
+    fn payout_approve(&mut self, payload: PayoutApprovalRequest) -> Handled {
+        let index = self.payout_index(&payload.tracking_id)?;
+        let payout = &self.payouts[index];
+        if payout.payout.nonce.as_deref() != Some(payload.nonce.as_str()) {
+            return Err(MockResponse::invalid("Invalid nonce."));
+        }
+        if payout.approved || payout.payout.status_code.as_deref() != Some("BP103") {
+            return Err(MockResponse::invalid("Payout is not awaiting approval."));
+        }
+
+        let total = payout.payout.total_amount_estimate.unwrap_or_default();
+        let wallet_id = payout.wallet_id.clone();
+        let balance = self
+            .wallet(&wallet_id)
+            .map(|wallet| wallet.available_balance)
+            .unwrap_or_default();
+        if balance < total {
+            return Err(MockResponse::invalid("Insufficient balance."));
+        }
+
+        let narrative = format!("Payout {}", payload.tracking_id);
+        self.post_transaction(
+            &wallet_id,
+            -total,
+            TransactionType::Payout,
+            &narrative,
+            None,
+        );
+
+        let wallet = self.wallet(&wallet_id).cloned();
+        let payout = &mut self.payouts[index];
+        payout.approved = true;
+        payout.payout.wallet = wallet;
+        payout.payout.status = Some("Processing".to_string());
+        payout.payout.status_code = Some("BP104".to_string());
+        Ok(MockResponse::ok(json!(payout.payout)))
+    }
+
+    fn payout_status(&mut self, payload: PayoutStatusRequest) -> Handled {
+        let index = self.payout_index(&payload.tracking_id)?;
+        let payout = &mut self.payouts[index];
+
+        if payout.payout.status_code.as_deref() == Some("BP104") {
+            payout.polls += 1;
+        }
+        if payout.polls >= self.scenario.transition_polls() {
+            payout.polls = 0;
+            payout.payout.status = Some("Completed".to_string());
+            payout.payout.status_code = Some("BC100".to_string());
+
+            let mut reversed = Decimal::ZERO;
+            for transaction in payout.payout.transactions.iter_mut().flatten() {
+                match self.scenario.payout_failure(&transaction.account) {
+                    Some(reason) => {
+                        transaction.status = Some(format!("Failed: {}", reason));
+                        transaction.status_code = Some("TF103".to_string());
+                        reversed += transaction.amount + PAYOUT_FEE;
+                    }
+                    None => {
+                        transaction.status = Some("Successful".to_string());
+                        transaction.status_code = Some("TS100".to_string());
+                    }
+                }
+            }
+
+            if reversed > Decimal::ZERO {
+                let wallet_id = payout.wallet_id.clone();
+                let narrative = format!("Payout reversal {}", payload.tracking_id);
+                self.post_transaction(
+                    &wallet_id,
+                    reversed,
+                    TransactionType::Adjustment,
+                    &narrative,
+                    None,
+                );
+            }
+        }
+        Ok(MockResponse::ok(json!(self.payouts[index].payout)))
+    }
+
+    fn payout_cancel(&mut self, payload: PayoutCancelRequest) -> Handled {
+        let payout = self
+            .payouts
+            .iter_mut()
+            .find(|payout| payout.payout.file_id.as_deref() == Some(payload.file_id.as_str()))
+            .ok_or_else(|| MockResponse::not_found("Payout not found."))?;
+        if payout.approved {
+            return Err(MockResponse::invalid(
+                "Approved payouts cannot be cancelled.",
+            ));
+        }
+
+        payout.payout.status = Some("Cancelled".to_string());
+        payout.payout.status_code = Some("BC110".to_string());
+        Ok(MockResponse::ok(json!(payout.payout)))
+    }
+
+    fn wallet_create(&mut self, payload: WalletCreateDetails) -> Handled {
+        let wallet_id = self.add_wallet(
+            payload.currency,
+            payload.wallet_type,
+            &payload.label,
+            payload.can_disburse,
+        );
+        Ok(MockResponse::created(json!(self.wallet(&wallet_id))))
+    }
+
+    fn intra_transfer(&mut self, wallet_id: &str, payload: WalletIntraTransferRequest) -> Handled {
+        let origin = self
+            .wallet(wallet_id)
+            .ok_or_else(|| MockResponse::not_found("Wallet not found."))?;
+        let destination = self
+            .wallet(&payload.wallet_id)
+            .ok_or_else(|| MockResponse::invalid("Destination wallet not found."))?;
+        if currency_code(&origin.currency) != currency_code(&destination.currency) {
+            return Err(MockResponse::invalid(
+                "Wallets must have the same currency.",
+            ));
+        }
+        if payload.amount <= Decimal::ZERO || origin.available_balance < payload.amount {
+            return Err(MockResponse::invalid("Insufficient balance."));
+        }
+
+        let (origin_id, destination_id) = (origin.wallet_id.clone(), destination.wallet_id.clone());
+        let narrative = payload.narrative;
+        self.post_transaction(
+            &origin_id,
+            -payload.amount,
+            TransactionType::Adjustment,
+            &narrative,
+            None,
+        );
+        self.post_transaction(
+            &destination_id,
+            payload.amount,
+            TransactionType::Adjustment,
+            &narrative,
+            None,
+        );
+
+        Ok(MockResponse::ok(json!({
+            "origin": self.wallet(&origin_id),
+            "destination": self.wallet(&destination_id),
+        })))
+    }
+
+    fn refund_create(&mut self, payload: RefundRequest) -> Handled {
+        let invoice = self
+            .invoice(&payload.invoice)
+            .ok_or_else(|| MockResponse::invalid("Invoice not found."))?;
+        if invoice.state != "COMPLETE" {
+            return Err(MockResponse::invalid(
+                "Only completed invoices can be refunded.",
+            ));
+        }
+
+        let refunded: Decimal = self
+            .refunds
+            .iter()
+            .filter(|refund| {
+                refund.transaction.invoice.as_ref().map(|i| &i.invoice_id) == Some(&payload.invoice)
+            })
+            .map(|refund| refund.amount)
+            .sum();
+        if payload.amount <= Decimal::ZERO || refunded + payload.amount > invoice.value {
+            return Err(MockResponse::invalid(
+                "Refund amount exceeds the invoice amount.",
+            ));
+        }
+
+        let now = self.now();
+        let chargeback_id = self.next_id("C");
+        let transaction = self
+            .transactions
+            .iter_mut()
+            .map(|(_, transaction)| transaction)
+            .find(|transaction| {
+                transaction.invoice.as_ref().map(|i| &i.invoice_id) == Some(&payload.invoice)
+            })
+            .ok_or_else(|| MockResponse::invalid("Invoice has no transaction."))?;
+        transaction.status = TransactionStatus::ChargebackPending;
+        transaction.updated_at = now.clone();
+
+        let refund = Refund {
+            chargeback_id,
+            session_id: Uuid::new_v4().to_string(),
+            transaction: transaction.clone(),
+            amount: payload.amount,
+            status: "PENDING".to_string(),
+            reason: payload.reason,
+            resolution: None,
+            staff_created: false,
+            created_at: now.clone(),
+            updated_at: now,
+        };
+
+        let response = json!(refund);
+        self.refunds.push(refund);
+        Ok(MockResponse::created(response))
+    }
+
+    fn payment_link_create(&mut self, payload: PaymentLinksCreateDetails) -> Handled {
+        if payload.title.trim().is_empty() {
+            return Err(MockResponse::invalid("title: This field may not be blank."));
+        }
+
+        let now = self.now();
+        let id = Uuid::new_v4();
+        let link = PaymentLink {
+            id,
+            title: payload.title,
+            is_active: payload.is_active.unwrap_or(true),
+            redirect_url: payload.redirect_url,
+            amount: Decimal::from(payload.amount.unwrap_or_default()),
+            usage_limit: payload.usage_limit.unwrap_or_default() as i32,
+            qrcode_file: None,
+            url: format!("{}/pay/{}/", self.host, id.simple()),
+            currency: currency_code(&payload.currency).to_string(),
+            mobile_tarrif: tarrif_code(payload.mobile_tarrif.as_ref()).to_string(),
+            card_tarrif: tarrif_code(payload.card_tarrif.as_ref()).to_string(),
+            created_at: Some(now.clone()),
+            updated_at: Some(now),
+        };
+
+        let response = json!(link);
+        self.payment_links.push(link);
+        Ok(MockResponse::created(response))
+    }
+
+    fn payment_link_update(&mut self, link_id: &str, payload: Option<JSON>) -> Handled {
+        let now = self.now();
+        let link = self
+            .payment_links
+            .iter_mut()
+            .find(|link| link.id.to_string() == link_id)
+            .ok_or_else(|| MockResponse::not_found("Payment link not found."))?;
+
+        let update = payload.unwrap_or(JSON::Null);
+        if let Some(title) = update["title"].as_str() {
+            link.title = title.to_string();
+        }
+        if let Some(amount) = update["amount"].as_i64() {
+            link.amount = Decimal::from(amount);
+        }
+        if let Some(usage_limit) = update["usage_limit"].as_i64() {
+            link.usage_limit = usage_limit as i32;
+        }
+        if let Some(is_active) = update["is_active"].as_bool() {
+            link.is_active = is_active;
+        }
+        if let Some(currency) = update["currency"].as_str() {
+            link.currency = currency.to_string();
+        }
+        if let Some(tarrif) = update["mobile_tarrif"].as_str() {
+            link.mobile_tarrif = tarrif.to_string();
+        }
+        if let Some(tarrif) = update["card_tarrif"].as_str() {
+            link.card_tarrif = tarrif.to_string();
+        }
+        if let Some(redirect_url) = update["redirect_url"].as_str() {
+            link.redirect_url = Some(redirect_url.to_string());
+        }
+        link.updated_at = Some(now);
+
+        Ok(MockResponse::ok(json!(link)))
+    }
+
+    fn payment_link(&self, link_id: &str) -> Option<&PaymentLink> {
+        self.payment_links
+            .iter()
+            .find(|link| link.id.to_string() == link_id)
+    }
+
+    fn invoice_index(&self, invoice_id: &str) -> Option<usize> {
+        self.invoices
+            .iter()
+            .position(|invoice| invoice.invoice.invoice_id == invoice_id)
+    }
+
+    fn payout_index(&self, tracking_id: &str) -> Result<usize, MockResponse> {
+        self.payouts
+            .iter()
+            .position(|payout| payout.payout.tracking_id.as_deref() == Some(tracking_id))
+            .ok_or_else(|| MockResponse::not_found("Payout not found."))
+    }
+
+    fn settlement_wallet(&self, currency: &Currency) -> Option<String> {
+        self.wallets
+            .iter()
+            .find(|wallet| {
+                matches!(wallet.wallet_type, WalletType::Settlement)
+                    && currency_code(&wallet.currency) == currency_code(currency)
+            })
+            .map(|wallet| wallet.wallet_id.clone())
+    }
+
+    fn add_wallet(
+        &mut self,
+        currency: Currency,
+        wallet_type: WalletType,
+        label: &str,
+        can_disburse: bool,
+    ) -> String {
+        let wallet_id = self.next_id("W");
+        let updated_at = self.now();
+        self.wallets.push(Wallet {
+            wallet_id: wallet_id.clone(),
+            label: label.to_string(),
+            can_disburse,
+            currency,
+            wallet_type,
+            current_balance: Decimal::ZERO,
+            available_balance: Decimal::ZERO,
+            updated_at,
+        });
+        wallet_id
+    }
+
+    /// Applies `value` to the balance of the wallet and records the transaction.
+    fn post_transaction(
+        &mut self,
+        wallet_id: &str,
+        value: Decimal,
+        trans_type: TransactionType,
+        narrative: &str,
+        invoice: Option<Invoice>,
+    ) {
+        let now = self.now();
+        let transaction_id = self.next_id("T");
+        let Some(wallet) = self
+            .wallets
+            .iter_mut()
+            .find(|wallet| wallet.wallet_id == wallet_id)
+        else {
+            return;
+        };
+
+        wallet.current_balance += value;
+        wallet.available_balance += value;
+        wallet.updated_at = now.clone();
+
+        let transaction = Transaction {
+            transaction_id,
+            invoice,
+            currency: wallet.currency.clone(),
+            value,
+            running_balance: wallet.current_balance,
+            narrative: Some(narrative.to_string()),
+            trans_type,
+            status: TransactionStatus::Available,
+            created_at: now.clone(),
+            updated_at: now,
+        };
+        self.transactions.push((wallet_id.to_string(), transaction));
+    }
+
+    fn next_id(&mut self, prefix: &str) -> String {
+        self.counter += 1;
+        format!("{}{:06}", prefix, self.counter)
+    }
+
+    /// A deterministic clock, advancing by a second for every event.
+    fn now(&mut self) -> String {
+        self.clock += 1;
+        let (minutes, seconds) = (self.clock / 60, self.clock % 60);
+        let (hours, minutes) = (minutes / 60, minutes % 60);
+        let (days, hours) = (hours / 24, hours % 24);
+        format!(
+            "2024-01-{:02}T{:02}:{:02}:{:02}.000000+03:00",
+            (1 + days).min(31),
+            hours,
+            minutes,
+            seconds
+        )
+    }
+}
+
+/// The STK push payload of `CollectionsAPI::mpesa_stk_push` and `WalletsAPI::fund_mpesa`
+#[derive(Deserialize)]
+struct StkPushPayload {
+    amount: Decimal,
+    phone_number: String,
+    api_ref: Option<String>,
+    wallet_id: Option<String>,
+    currency: Option<Currency>,
+}
+
+/// The checkout payload of `CheckoutsAPI::initiate` and `WalletsAPI::fund_checkout`
+#[derive(Deserialize)]
+struct CheckoutPayload {
+    amount: Decimal,
+    currency: Currency,
+    first_name: Option<String>,
+    last_name: Option<String>,
+    email: Option<String>,
+    phone_number: Option<String>,
+    method: Option<CheckoutMethod>,
+    country: Option<String>,
+    address: Option<String>,
+    city: Option<String>,
+    state: Option<String>,
+    zipcode: Option<String>,
+    api_ref: Option<String>,
+    wallet_id: Option<String>,
+    redirect_url: Option<String>,
+    host: Option<String>,
+    mobile_tarrif: Option<Tarrif>,
+    card_tarrif: Option<Tarrif>,
+    bitcoin_tarrif: Option<Tarrif>,
+    ach_tarrif: Option<Tarrif>,
+}
+
+#[derive(Deserialize)]
+struct CheckoutDetailsPayload {
+    checkout_id: String,
+    signature: String,
+}
+
+fn body<T: DeserializeOwned>(request: &MockRequest) -> Result<T, MockResponse> {
+    serde_json::from_value(request.body.clone().unwrap_or(JSON::Null))
+        .map_err(|error| MockResponse::invalid(&error.to_string()))
+}
+
+fn page<T: serde::Serialize>(results: &[T]) -> JSON {
+    json!({
+        "count": results.len(),
+        "next": null,
+        "previous": null,
+        "results": results,
+    })
+}
+
+fn unauthenticated() -> MockResponse {
+    MockResponse::error(
+        401,
+        "not_authenticated",
+        "Authentication credentials were not provided.",
+    )
+}
+
+fn currency_code(currency: &Currency) -> &'static str {
+    match currency {
+        Currency::Kes => "KES",
+        Currency::Usd => "USD",
+        Currency::Eur => "EUR",
+        Currency::Gbp => "GBP",
+    }
+}
+
+fn tarrif_code(tarrif: Option<&Tarrif>) -> &'static str {
+    match tarrif {
+        Some(Tarrif::CustomerPays) => "CUSTOMER-PAYS",
+        _ => "BUSINESS-PAYS",
+    }
+}
@@ -0,0 +1,212 @@
+//! HTTP transports of the `Intasend` client.
+//!
+//! A transport sends a fully formed [`ApiRequest`] (credentials included) and returns the
+//! [`ApiResponse`]. By default the client uses [`ReqwestTransport`].
+//!
+//! With the `tower` feature enabled, `ReqwestTransport` is a
+//! [`tower::Service`](https://docs.rs/tower), and any service of `ApiRequest`s can be
+//! plugged into the client with `Intasend::with_service`. This way the standard tower
+//! layers (timeouts, concurrency limits, rate limiting, load shedding...) or in-house ones
+//! can be composed with the SDK, and every `*API` facade runs on top of the resulting
+//! service:
+//!
+//! ```rust
+//! # #[cfg(feature = "tower")]
+//! # {
+//! use std::time::Duration;
+//! use tower::ServiceBuilder;
+//!
+//! let service = ServiceBuilder::new()
+//!     .timeout(Duration::from_secs(30))
+//!     .concurrency_limit(8)
+//!     .service(intasend::transport::ReqwestTransport::new());
+//!
+//! let intasend = intasend::Intasend::new(
+//!     "publishable_key".to_string(),
+//!     "secret_key".to_string(),
+//!     true,
+//! )
+//! .with_service(service);
+//! # }
+//! ```
+
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+#[cfg(feature = "tower")]
+use std::task::{Context, Poll};
+
+use futures::FutureExt;
+use reqwest::Client;
+
+use super::runtime::HttpExchange;
+use super::{ApiRequest, ApiResponse, IntasendClientError};
+
+/// Sends `ApiRequest`s to the IntaSend API
+pub(crate) trait Transport: Debug + Send + Sync + 'static {
+    fn send(&self, request: ApiRequest) -> HttpExchange<'static>;
+}
+
+pub(crate) fn default_transport() -> Arc<dyn Transport> {
+    Arc::new(ReqwestTransport::new())
+}
+
+/// `ReqwestTransport` - sends the requests with a (shared) `reqwest::Client`
+#[derive(Clone, Debug, Default)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    /// Creates a transport with a default `reqwest::Client`
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Uses a custom configured client, e.g. with proxies or connection pool settings.
+impl From<Client> for ReqwestTransport {
+    fn from(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(&self, request: ApiRequest) -> HttpExchange<'static> {
+        let client = self.client.clone();
+
+        async move {
+            let mut builder = client
+                .request(request.method, request.url)
+                .headers(request.headers);
+            if let Some(body) = &request.body {
+                builder = builder.json(body);
+            }
+
+            let response = builder.send().await?;
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.bytes().await?.to_vec();
+
+            Ok(ApiResponse {
+                status,
+                headers,
+                body,
+            })
+        }
+        .boxed()
+    }
+}
+
+#[cfg(feature = "tower")]
+impl tower_service::Service<ApiRequest> for ReqwestTransport {
+    type Response = ApiResponse;
+    type Error = IntasendClientError;
+    type Future = HttpExchange<'static>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: ApiRequest) -> Self::Future {
+        Transport::send(self, request)
+    }
+}
+
+/// Adapts a tower service to a `Transport`, cloning the service for every request.
+#[cfg(feature = "tower")]
+pub(crate) struct ServiceTransport<S>(pub(crate) S);
+
+#[cfg(feature = "tower")]
+impl<S> Transport for ServiceTransport<S>
+where
+    S: tower_service::Service<ApiRequest, Response = ApiResponse> + Clone + Send + Sync + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    S::Future: Send,
+{
+    fn send(&self, request: ApiRequest) -> HttpExchange<'static> {
+        let mut service = self.0.clone();
+
+        async move {
+            futures::future::poll_fn(|cx| service.poll_ready(cx))
+                .await
+                .map_err(into_client_error)?;
+            service.call(request).await.map_err(into_client_error)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(feature = "tower")]
+impl<S> Debug for ServiceTransport<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ServiceTransport")
+            .field(&std::any::type_name::<S>())
+            .finish()
+    }
+}
+
+/// Keeps the errors of the SDK's own services as they are, and wraps the others.
+#[cfg(feature = "tower")]
+fn into_client_error<E>(error: E) -> IntasendClientError
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    match error.into().downcast::<IntasendClientError>() {
+        Ok(error) => *error,
+        Err(error) => IntasendClientError::Transport(error),
+    }
+}
+
+#[cfg(all(test, feature = "tower", feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::Intasend;
+    use reqwest::header::{HeaderMap, AUTHORIZATION};
+    use reqwest::StatusCode;
+
+    fn intasend() -> Intasend {
+        Intasend::new("pk".to_string(), "sk".to_string(), true)
+    }
+
+    #[tokio::test]
+    async fn facades_run_on_the_configured_service() {
+        let service = tower::service_fn(|request: ApiRequest| async move {
+            assert_eq!(request.path(), "/api/v1/wallets/");
+            assert_eq!(request.headers[AUTHORIZATION], "Bearer sk");
+
+            Ok::<_, IntasendClientError>(ApiResponse {
+                status: StatusCode::OK,
+                headers: HeaderMap::new(),
+                body: br#"{"count": 0, "next": null, "previous": null, "results": []}"#.to_vec(),
+            })
+        });
+
+        let wallets = intasend()
+            .with_service(service)
+            .wallets()
+            .list()
+            .await
+            .unwrap();
+        assert_eq!(wallets.count, 0);
+    }
+
+    #[tokio::test]
+    async fn service_errors_are_transport_errors() {
+        let service = tower::ServiceBuilder::new()
+            .timeout(std::time::Duration::from_millis(10))
+            .service(tower::service_fn(|_: ApiRequest| async {
+                futures::future::pending::<Result<ApiResponse, IntasendClientError>>().await
+            }));
+
+        let error = intasend()
+            .with_service(service)
+            .wallets()
+            .list()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<IntasendClientError>(),
+            Some(IntasendClientError::Transport(_))
+        ));
+    }
+}
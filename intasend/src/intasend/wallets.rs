@@ -7,7 +7,7 @@ use serde_json::{json, Value as JSON};
 
 use crate::{Currency, Intasend, Transaction};
 
-use super::{Customer, Invoice, Provider, RequestClient, RequestMethods};
+use super::{de, Customer, Invoice, Provider, RequestClient, RequestMethods};
 
 /// `WalletsAPI` struct implements methods for facilitating:
 /// listing all wallets managed with an API key, get details
@@ -313,6 +313,8 @@ impl WalletsAPI {
                 request_method,
             )
             .await?;
+        #[cfg(feature = "metrics")]
+        super::telemetry::record_stk_push_initiated();
 
         Ok(fund_mpesa_response.clone())
     }
@@ -381,6 +383,7 @@ impl WalletsAPI {
 pub struct Wallet {
     pub wallet_id: String,
     pub label: String,
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub can_disburse: bool,
     pub currency: Currency,
     pub wallet_type: WalletType,
@@ -404,8 +407,11 @@ pub struct WalletListRequest {}
 /// `WalletListResponse` struct
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WalletListResponse {
+    #[serde(default, deserialize_with = "de::lenient_number")]
     pub count: u32,
+    #[serde(default, deserialize_with = "de::lenient_string")]
     pub next: Option<String>,
+    #[serde(default, deserialize_with = "de::lenient_string")]
     pub previous: Option<String>,
     pub results: Vec<Wallet>,
 }
@@ -435,8 +441,11 @@ struct WalletTransanctionsRequest {}
 /// `WalletTransactionsResponse` struct
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WalletTransactionsResponse {
+    #[serde(default, deserialize_with = "de::lenient_number")]
     pub count: u32,
+    #[serde(default, deserialize_with = "de::lenient_string")]
     pub next: Option<String>,
+    #[serde(default, deserialize_with = "de::lenient_string")]
     pub previous: Option<String>,
     pub results: Vec<Transaction>,
 }
@@ -472,6 +481,7 @@ pub struct FundMpesaResponse {
     pub invoice: Option<Invoice>,
     pub customer: Option<Customer>,
     pub payment_link: Option<String>,
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub refundable: bool,
     pub created_at: String,
     pub updated_at: String,
@@ -496,6 +506,7 @@ pub struct FundCheckoutRequest {
 /// **Note**: persist the `id` and the `signature` field in a store if you want to get the details of the fund checkout
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FundCheckoutResponse {
+    #[serde(default, deserialize_with = "de::null_as_default")]
     pub paid: bool,
     pub id: String,
     pub url: String,
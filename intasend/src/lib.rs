@@ -5,9 +5,16 @@ mod intasend;
 // Checkout functionality - available for both `client` and `server` environments
 #[cfg(any(feature = "client", feature = "server"))]
 pub use self::intasend::checkout::{
-    CheckoutDetailsRequest, CheckoutDetailsResponse, CheckoutMethod, CheckoutRequest,
-    CheckoutResponse, CheckoutsAPI,
+    CheckoutDetailsRequest, CheckoutDetailsResponse, CheckoutEvent, CheckoutMethod,
+    CheckoutRequest, CheckoutRequestBuilder, CheckoutResponse, CheckoutSignature,
+    CheckoutWaitOptions, CheckoutsAPI,
 };
+#[cfg(any(feature = "client", feature = "server"))]
+pub use self::intasend::checkout_button::{CheckoutButton, INLINE_SDK_URL};
+
+// QR codes of the payment URLs - `qrcode` feature
+#[cfg(feature = "qrcode")]
+pub use self::intasend::qr_code::{QrCode, QrErrorCorrection};
 
 // Collection functionality - `server` only
 #[cfg(feature = "server")]
@@ -23,6 +30,14 @@ pub use self::intasend::payouts::{
     PayoutResponseTransaction, PayoutStatusRequest, PayoutsAPI,
 };
 
+// Payouts from CSV and JSON files, split in batches and approved by policy - `server` only
+#[cfg(feature = "server")]
+pub use self::intasend::payouts::approval as payout_approval;
+#[cfg(feature = "server")]
+pub use self::intasend::payouts::batch as payout_batch;
+#[cfg(feature = "server")]
+pub use self::intasend::payouts::import as payout_import;
+
 // Refund functionality - `server` only
 #[cfg(feature = "server")]
 pub use self::intasend::refunds::{
@@ -40,6 +55,10 @@ pub use self::intasend::wallets::{
 
 // Payment Links functionality - `server` only
 #[cfg(feature = "server")]
+pub use self::intasend::payment_link_analytics::PaymentLinkAnalytics;
+#[cfg(feature = "server")]
+pub use self::intasend::payment_link_template::PaymentLinkTemplate;
+#[cfg(feature = "server")]
 pub use self::intasend::payment_links::{
     PaymentLink, PaymentLinksAPI, PaymentLinksCreateDetails, PaymentLinksDetailsRequest,
     PaymentLinksListRequest, PaymentLinksListResponse, PaymentLinksUpdateDetails,
@@ -48,10 +67,44 @@ pub use self::intasend::payment_links::{
 // Core types - available for both `client` and `server` environments
 #[cfg(any(feature = "client", feature = "server"))]
 pub use self::intasend::{
-    Currency, Intasend, IntasendApiError, IntasendApiErrorDetail, IntasendClientError,
-    PayoutProvider, Provider, Tarrif, Transaction, TransactionStatus, TransactionType,
+    ApiRequest, ApiResponse, CardInfo, Currency, Customer, Intasend, IntasendApiError,
+    IntasendApiErrorDetail, IntasendClientError, Invoice, PayoutProvider, Provider, Tarrif,
+    Transaction, TransactionStatus, TransactionType,
 };
 
+// Request/response middleware - available for both `client` and `server` environments
+pub use self::intasend::middleware;
+
+// HTTP transports - available for both `client` and `server` environments
+pub use self::intasend::transport;
+
+// Client side rate limiting - available for both `client` and `server` environments
+pub use self::intasend::rate_limit;
+
+// Checkout session persistence - available for both `client` and `server` environments
+pub use self::intasend::checkout_store;
+
+// Idempotency of the payment creating calls - available for both `client` and `server` environments
+pub use self::intasend::idempotency;
+
+// Mock IntaSend server for hermetic tests - `testing` feature
+#[cfg(feature = "testing")]
+pub use self::intasend::testing;
+
+// Builders and JSON samples of the response models - `test-utils` feature
+#[cfg(feature = "test-utils")]
+pub use self::intasend::fixtures;
+
+// Async runtime adapters - available for every runtime feature
+pub use self::intasend::runtime;
+
+// Tracing & metrics documentation - `tracing` and `metrics` features
+pub use self::intasend::telemetry;
+
+// Blocking (synchronous) client - `blocking` feature
+#[cfg(feature = "blocking")]
+pub use self::intasend::blocking;
+
 #[cfg(test)]
 mod tests {
     use super::*;